@@ -0,0 +1,238 @@
+//! Generates the block state registry from the vanilla blocks report, and
+//! the light and collision of every state from the physics table.
+// The report comes from the 1.15.2 server jar:
+// java -cp server.jar net.minecraft.data.Main --reports
+
+use serde_json::{Map, Value};
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const BLOCKS_REPORT: &str = "data/blocks.json";
+/// Blocks which are not full opaque solid blocks giving off no light, keyed
+/// by name. `blocks_motion` and `light_opacity` hold for every state, while
+/// `light_emission` is either a level or a map of conditions such as
+/// `lit=true` to the level of the states meeting them.
+const PHYSICS_TABLE: &str = "data/block_physics.json";
+
+struct Block {
+    name: String,
+    properties: Vec<(String, Vec<String>)>,
+    first_state: u64,
+    default_state: u64,
+    blocks_motion: bool,
+    light_opacity: u64,
+    /// The block light of each state, from the first one.
+    light_emission: Vec<u64>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", BLOCKS_REPORT);
+    println!("cargo:rerun-if-changed={}", PHYSICS_TABLE);
+    let report = fs::read_to_string(BLOCKS_REPORT).expect("blocks report is readable");
+    let report: Map<String, Value> = serde_json::from_str(&report).expect("blocks report is valid JSON");
+
+    let mut blocks: Vec<Block> = report.iter().map(|(name, block)| parse_block(name, block)).collect();
+    blocks.sort_by_key(|block| block.first_state);
+    let mut next_state = 0;
+    for block in blocks.iter() {
+        assert_eq!(block.first_state, next_state, "state ids of {} are not contiguous", block.name);
+        next_state += state_count(block);
+    }
+
+    let table = fs::read_to_string(PHYSICS_TABLE).expect("physics table is readable");
+    let table: Map<String, Value> = serde_json::from_str(&table).expect("physics table is valid JSON");
+    for (name, physics) in table.iter() {
+        let block = blocks.iter_mut().find(|block| block.name == *name);
+        let block = block.unwrap_or_else(|| panic!("{} of the physics table is not a block", name));
+        apply_physics(block, physics);
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("blocks.rs");
+    fs::write(out, generate(&blocks, next_state)).expect("generated blocks are writable");
+}
+
+fn parse_block(name: &str, block: &Value) -> Block {
+    let properties: Vec<(String, Vec<String>)> = match block.get("properties") {
+        Some(Value::Object(properties)) => properties
+            .iter()
+            .map(|(property, values)| {
+                let values = values
+                    .as_array()
+                    .expect("property values are an array")
+                    .iter()
+                    .map(|value| value.as_str().expect("property values are strings").to_owned())
+                    .collect();
+                (property.clone(), values)
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let states = block["states"].as_array().expect("states are an array");
+    let first_state = states.iter().map(|state| state["id"].as_u64().unwrap()).min().unwrap();
+    let mut default_state = first_state;
+    for state in states.iter() {
+        let id = state["id"].as_u64().expect("state ids are integers");
+        // the registry finds states from their properties, the ids must follow their order
+        assert_eq!(
+            id - first_state,
+            state_offset(&properties, &state["properties"]),
+            "state {} of {} is out of order",
+            id,
+            name
+        );
+        if state.get("default").and_then(Value::as_bool).unwrap_or(false) {
+            default_state = id;
+        }
+    }
+    let count = properties.iter().map(|(_, values)| values.len()).product();
+    Block {
+        name: name.to_owned(),
+        properties,
+        first_state,
+        default_state,
+        blocks_motion: true,
+        light_opacity: 15,
+        light_emission: vec![0; count],
+    }
+}
+
+fn apply_physics(block: &mut Block, physics: &Value) {
+    for (field, value) in physics.as_object().expect("physics are objects").iter() {
+        match (field.as_str(), value) {
+            ("blocks_motion", Value::Bool(blocks_motion)) => block.blocks_motion = *blocks_motion,
+            ("light_opacity", Value::Number(_)) => block.light_opacity = light_level(value),
+            ("light_emission", Value::Number(_)) => {
+                let emission = light_level(value);
+                block.light_emission.iter_mut().for_each(|level| *level = emission);
+            }
+            ("light_emission", Value::Object(conditions)) => {
+                for (condition, emission) in conditions.iter() {
+                    let emission = light_level(emission);
+                    let mut met = false;
+                    for offset in 0..block.light_emission.len() {
+                        if meets(&block.properties, offset, condition) {
+                            block.light_emission[offset] = emission;
+                            met = true;
+                        }
+                    }
+                    assert!(met, "no state of {} is {}", block.name, condition);
+                }
+            }
+            _ => panic!("{} of {} is not a physics field", field, block.name),
+        }
+    }
+}
+
+fn light_level(level: &Value) -> u64 {
+    let level = level.as_u64().expect("light levels are integers");
+    assert!(level <= 15, "light level {} is over 15", level);
+    level
+}
+
+/// Returns whether the state at `offset` has every `property=value` of the
+/// comma separated `condition`.
+fn meets(properties: &[(String, Vec<String>)], offset: usize, condition: &str) -> bool {
+    let mut values = Vec::with_capacity(properties.len());
+    let mut offset = offset;
+    for (property, possible) in properties.iter().rev() {
+        values.push((property.as_str(), possible[offset % possible.len()].as_str()));
+        offset /= possible.len();
+    }
+    condition.split(',').all(|pair| {
+        let (property, value) = pair.split_once('=').expect("conditions are property=value pairs");
+        assert!(
+            properties.iter().any(|(name, possible)| name == property && possible.iter().any(|v| v == value)),
+            "{} is not a possible property value",
+            pair
+        );
+        values.contains(&(property, value))
+    })
+}
+
+/// Offset of a state from the first state of its block, the last property
+/// changing fastest.
+fn state_offset(properties: &[(String, Vec<String>)], values: &Value) -> u64 {
+    properties.iter().fold(0, |offset, (property, possible)| {
+        let value = values[property.as_str()].as_str().expect("states have every property");
+        let index = possible.iter().position(|v| v == value).expect("values are possible");
+        offset * possible.len() as u64 + index as u64
+    })
+}
+
+fn state_count(block: &Block) -> u64 {
+    block.properties.iter().map(|(_, values)| values.len() as u64).product()
+}
+
+/// `minecraft:oak_stairs` becomes `OakStairs`.
+fn variant_name(name: &str) -> String {
+    name.trim_start_matches("minecraft:")
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn generate(blocks: &[Block], state_count: u64) -> String {
+    let mut out = String::new();
+    writeln!(out, "/// Number of block states, the highest global state id plus one.").unwrap();
+    writeln!(out, "pub const STATE_COUNT: u16 = {};", state_count).unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "/// Every block of the game, regardless of its properties.").unwrap();
+    writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum BlockKind {{").unwrap();
+    for block in blocks.iter() {
+        writeln!(out, "    {},", variant_name(&block.name)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "impl BlockKind {{").unwrap();
+    writeln!(out, "    /// Returns the kind named `name`, such as `minecraft:stone`.").unwrap();
+    writeln!(out, "    pub fn from_name(name: &str) -> Option<BlockKind> {{").unwrap();
+    writeln!(out, "        let kind = match name {{").unwrap();
+    for block in blocks.iter() {
+        writeln!(out, "            {:?} => BlockKind::{},", block.name, variant_name(&block.name)).unwrap();
+    }
+    writeln!(out, "            _ => return None,").unwrap();
+    writeln!(out, "        }};").unwrap();
+    writeln!(out, "        Some(kind)").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "/// Blocks in the order of their state ids, indexed by `BlockKind`.").unwrap();
+    writeln!(out, "static BLOCKS: [BlockInfo; {}] = [", blocks.len()).unwrap();
+    for block in blocks.iter() {
+        writeln!(out, "    BlockInfo {{").unwrap();
+        writeln!(out, "        kind: BlockKind::{},", variant_name(&block.name)).unwrap();
+        writeln!(out, "        name: {:?},", block.name).unwrap();
+        writeln!(out, "        first_state: {},", block.first_state).unwrap();
+        writeln!(out, "        default_state: {},", block.default_state).unwrap();
+        writeln!(out, "        blocks_motion: {},", block.blocks_motion).unwrap();
+        writeln!(out, "        light_opacity: {},", block.light_opacity).unwrap();
+        writeln!(out, "        properties: &[").unwrap();
+        for (name, values) in block.properties.iter() {
+            writeln!(out, "            Property {{ name: {:?}, values: &{:?} }},", name, values).unwrap();
+        }
+        writeln!(out, "        ],").unwrap();
+        writeln!(out, "    }},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "/// Block light given off by each state, indexed by state id.").unwrap();
+    writeln!(out, "static LIGHT_EMISSION: [u8; {}] = [", state_count).unwrap();
+    let emissions: Vec<u64> = blocks.iter().flat_map(|block| block.light_emission.iter().copied()).collect();
+    for line in emissions.chunks(32) {
+        let line: Vec<String> = line.iter().map(u64::to_string).collect();
+        writeln!(out, "    {},", line.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    out
+}
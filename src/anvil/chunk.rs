@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::anvil::AnvilErr;
+use crate::biome::Biome;
+use crate::block::BlockState;
+use crate::nbt::{self, ByteArray, Compound, IntArray, LongArray, NbtErr, Tag};
+use crate::packet::block_entity::BlockEntity;
+use crate::packet::chunk::{Biomes, ChunkColumn, ChunkSection, HeightMaps, BIOME_COUNT, SECTION_COUNT};
+use crate::packet::light::{ChunkLight, LightArray, LightKind};
+
+/// Oldest data version with block palettes, the one of 1.13.
+const FLATTENING_DATA_VERSION: i32 = 1451;
+/// Data version chunks are saved with, the one of 1.15.2.
+const DATA_VERSION: i32 = 2230;
+/// Tags of `Level` read into the column, the others are kept as extra tags.
+const LEVEL_TAGS: &[&str] = &[
+    "xPos", "zPos", "Status", "isLightOn", "Sections", "Biomes", "Heightmaps", "TileEntities",
+];
+
+/// The `Level` compound of a chunk, but its heightmaps and block entities
+/// which are read on their own. Other tags are kept as they are.
+// see https://minecraft.gamepedia.com/Chunk_format
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Level {
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    /// Generation step the chunk went through, `full` once it is over.
+    status: String,
+    #[serde(rename = "isLightOn", default)]
+    is_light_on: bool,
+    #[serde(default)]
+    sections: Vec<Section>,
+    biomes: Option<IntArray>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Section {
+    /// From -1 below the world to 16 above it, for light.
+    y: i8,
+    palette: Option<Vec<PaletteEntry>>,
+    /// Indices into the palette, with as few bits as it needs but at least 4.
+    block_states: Option<LongArray>,
+    block_light: Option<ByteArray>,
+    sky_light: Option<ByteArray>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PaletteEntry {
+    name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, String>,
+}
+
+impl PaletteEntry {
+    fn new(state: BlockState) -> Self {
+        Self {
+            name: state.name().to_owned(),
+            properties: state
+                .properties()
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        }
+    }
+
+    fn state(&self) -> Result<BlockState, AnvilErr> {
+        let properties: Vec<(&str, &str)> =
+            self.properties.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        BlockState::from_name(&self.name, &properties)
+            .ok_or_else(|| AnvilErr::UnknownBlock(format!("{}{:?}", self.name, self.properties)))
+    }
+}
+
+/// Converts the root tag of a chunk saved at `location`, `None` if the chunk
+/// is not fully generated.
+pub fn column_from_tag(tag: Tag, location: (i32, i32)) -> Result<Option<ChunkColumn>, AnvilErr> {
+    let malformed = |message: &str| AnvilErr::Malformed(format!("{} in chunk {:?}", message, location));
+    let mut root = match tag {
+        Tag::Compound(root) => root,
+        _ => return Err(malformed("no root compound")),
+    };
+    // newer versions may save blocks, biomes and light differently
+    match root.get("DataVersion") {
+        Some(Tag::Int(version)) if *version > DATA_VERSION => return Err(AnvilErr::NewerDataVersion(*version)),
+        Some(Tag::Int(version)) if *version >= FLATTENING_DATA_VERSION => (),
+        _ => return Err(malformed("data older than 1.13")),
+    }
+    let mut level = match root.shift_remove("Level") {
+        Some(Tag::Compound(level)) => level,
+        _ => return Err(malformed("no Level compound")),
+    };
+    let heightmaps = level.shift_remove("Heightmaps");
+    let block_entities = match level.shift_remove("TileEntities") {
+        Some(Tag::List(block_entities)) => block_entities,
+        _ => Vec::new(),
+    };
+    let (level, extra_tags): (Compound, Compound) =
+        level.into_iter().partition(|(name, _)| LEVEL_TAGS.contains(&name.as_str()));
+    let level: Level = nbt::from_tag(Tag::Compound(level))?;
+    if level.status != "full" {
+        return Ok(None);
+    }
+    if (level.x_pos, level.z_pos) != location {
+        return Err(malformed(&format!("position ({}, {})", level.x_pos, level.z_pos)));
+    }
+
+    let mut column = ChunkColumn::empty(location);
+    let mut light = ChunkLight::new();
+    for section in level.sections.into_iter() {
+        let y = section.y as i32;
+        if !(-1..=SECTION_COUNT as i32).contains(&y) {
+            return Err(malformed(&format!("section {}", y)));
+        }
+        if let (Some(palette), Some(block_states)) = (section.palette, section.block_states) {
+            if !(0..SECTION_COUNT as i32).contains(&y) {
+                return Err(malformed(&format!("blocks in section {}", y)));
+            }
+            let palette = palette.iter().map(PaletteEntry::state).collect::<Result<Vec<_>, _>>()?;
+            let data = block_states.0.into_iter().map(|d| d as u64).collect();
+            let chunk_section = ChunkSection::from_palette(&palette, data)
+                .ok_or_else(|| malformed(&format!("block states of section {}", y)))?;
+            column.set_section(y as usize, Some(chunk_section));
+        }
+        let arrays = [(LightKind::Block, section.block_light), (LightKind::Sky, section.sky_light)];
+        for (kind, array) in arrays.iter().cloned() {
+            if let Some(array) = array {
+                let array = LightArray::from_bytes(array.0.into_iter().map(|b| b as u8).collect())
+                    .ok_or_else(|| malformed(&format!("light of section {}", y)))?;
+                light.set_section(kind, y, array);
+            }
+        }
+    }
+    if level.is_light_on {
+        column.set_light(light);
+    }
+
+    match level.biomes {
+        Some(ids) if ids.0.len() == BIOME_COUNT => {
+            *column.biomes_mut() = Biomes::try_from(ids).map_err(|e| malformed(&e))?;
+        }
+        // before 1.15 a single biome is saved for each column of blocks
+        Some(ids) if ids.0.len() == 256 => {
+            let biomes = column.biomes_mut();
+            for (index, id) in ids.0.iter().enumerate() {
+                let biome = Biome::from_id(*id).ok_or_else(|| malformed(&format!("biome {}", id)))?;
+                for y in (0..SECTION_COUNT * 16).step_by(4) {
+                    biomes.set(index & 15, y, index >> 4, biome);
+                }
+            }
+        }
+        Some(ids) => return Err(malformed(&format!("{} biomes", ids.0.len()))),
+        None => (),
+    }
+
+    // vanilla may leave heightmaps out or have other ones, they can be computed again
+    let heightmaps = heightmaps.and_then(|heightmaps| nbt::from_tag::<HeightMaps>(heightmaps).ok());
+    if !heightmaps.is_some_and(|heightmaps| column.load_heightmaps(&heightmaps)) {
+        column.compute_heightmaps();
+    }
+
+    for tag in block_entities.into_iter() {
+        let block_entity = BlockEntity::from_tag(tag)?;
+        column.block_entities_mut().insert(block_entity);
+    }
+    column.set_extra_tags(extra_tags);
+    column.mark_saved();
+    Ok(Some(column))
+}
+
+/// Converts a column to the root tag it is saved as, the reverse of
+/// `column_from_tag`.
+pub fn column_to_tag(column: &ChunkColumn) -> Result<Tag, NbtErr> {
+    let mut sections = Vec::new();
+    for y in -1..=SECTION_COUNT as i32 {
+        let blocks = if (0..SECTION_COUNT as i32).contains(&y) {
+            column.section(y as usize).map(ChunkSection::to_palette)
+        } else {
+            None
+        };
+        let light = |kind| {
+            let array = column.light().section(kind, y).inner();
+            ByteArray(array.iter().map(|b| *b as i8).collect())
+        };
+        if blocks.is_none() && !column.is_lit() {
+            continue;
+        }
+        let (palette, block_states) = match blocks {
+            Some((palette, data)) => (
+                Some(palette.into_iter().map(PaletteEntry::new).collect()),
+                Some(LongArray(data.into_iter().map(|d| d as i64).collect())),
+            ),
+            None => (None, None),
+        };
+        sections.push(Section {
+            y: y as i8,
+            palette,
+            block_states,
+            block_light: Some(light(LightKind::Block)).filter(|_| column.is_lit()),
+            sky_light: Some(light(LightKind::Sky)).filter(|_| column.is_lit()),
+        });
+    }
+    let (x_pos, z_pos) = column.location();
+    let level = Level {
+        x_pos,
+        z_pos,
+        status: String::from("full"),
+        is_light_on: column.is_lit(),
+        sections,
+        biomes: Some(IntArray::from(column.biomes().clone())),
+    };
+    let mut level = match nbt::to_tag(&level)? {
+        Tag::Compound(level) => level,
+        _ => unreachable!("structs are serialized as compounds"),
+    };
+    level.insert(String::from("Heightmaps"), nbt::to_tag(&HeightMaps::new(column))?);
+    let block_entities = column.block_entities().iter().map(BlockEntity::to_tag);
+    level.insert(String::from("TileEntities"), Tag::List(block_entities.collect::<Result<_, _>>()?));
+    for (name, tag) in column.extra_tags().iter() {
+        if !LEVEL_TAGS.contains(&name.as_str()) {
+            level.insert(name.clone(), tag.clone());
+        }
+    }
+    level.entry(String::from("Entities")).or_insert_with(|| Tag::List(Vec::new()));
+
+    let mut root = Compound::new();
+    root.insert(String::from("DataVersion"), Tag::Int(DATA_VERSION));
+    root.insert(String::from("Level"), Tag::Compound(level));
+    Ok(Tag::Compound(root))
+}
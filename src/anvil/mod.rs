@@ -0,0 +1,29 @@
+//! Worlds saved in the Anvil format of vanilla, chunks grouped 32 by 32 in
+//! region files.
+// see https://minecraft.gamepedia.com/Region_file_format
+
+mod chunk;
+mod region;
+
+pub use chunk::{column_from_tag, column_to_tag};
+pub use region::{Region, RegionStorage};
+
+use thiserror::Error;
+
+use crate::nbt::NbtErr;
+
+#[derive(Debug, Error)]
+pub enum AnvilErr {
+    #[error("Could not access region file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Nbt(#[from] NbtErr),
+    #[error("Unknown chunk compression {0}.")]
+    UnknownCompression(u8),
+    #[error("Unknown block {0}.")]
+    UnknownBlock(String),
+    #[error("Malformed chunk: {0}")]
+    Malformed(String),
+    #[error("Chunk saved by a newer version of Minecraft, data version {0}.")]
+    NewerDataVersion(i32),
+}
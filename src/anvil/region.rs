@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::anvil::{
+    chunk::{column_from_tag, column_to_tag},
+    AnvilErr,
+};
+use crate::nbt::{self, Compression, Tag};
+use crate::packet::chunk::ChunkColumn;
+
+/// Region files are split in sectors of 4KiB.
+const SECTOR_LENGTH: usize = 4096;
+/// Chunks of a region along each axis.
+const REGION_WIDTH: i32 = 32;
+const CHUNK_COUNT: usize = (REGION_WIDTH * REGION_WIDTH) as usize;
+/// Set on the compression of chunks saved in their own `.mcc` file.
+const EXTERNAL_FLAG: u8 = 0x80;
+/// Compression chunks are saved with.
+const ZLIB: u8 = 2;
+/// Most sectors a chunk can span, larger ones are saved in their own file.
+const MAX_SECTORS: usize = 255;
+
+/// A region file, holding the chunks of a 32x32 area. Chunks are only read
+/// once they are asked for and written as soon as they are saved.
+pub struct Region {
+    path: PathBuf,
+    file: File,
+    /// For each chunk, its first sector shifted left by 8 bits along with its
+    /// sector count, 0 if the chunk is not saved.
+    locations: [u32; CHUNK_COUNT],
+    /// For each chunk, when it was last saved in seconds since the epoch.
+    timestamps: [u32; CHUNK_COUNT],
+}
+
+impl Region {
+    /// Opens a region file, creating it if it does not exist.
+    pub fn open(path: &Path) -> Result<Self, AnvilErr> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let mut header = vec![0; 2 * SECTOR_LENGTH];
+        let length = file.metadata()?.len() as usize;
+        if length >= header.len() {
+            file.read_exact(&mut header)?;
+        } else if length == 0 {
+            file.write_all(&header)?;
+        } else {
+            return Err(AnvilErr::Malformed(format!("{} has no complete header", path.display())));
+        }
+        let mut locations = [0; CHUNK_COUNT];
+        let mut timestamps = [0; CHUNK_COUNT];
+        for (index, entry) in header.chunks_exact(4).enumerate() {
+            let value = u32::from_be_bytes(entry.try_into().unwrap());
+            if index < CHUNK_COUNT {
+                locations[index] = value;
+            } else {
+                timestamps[index - CHUNK_COUNT] = value;
+            }
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            locations,
+            timestamps,
+        })
+    }
+
+    /// Returns the name of the region file holding a chunk, such as
+    /// `r.-1.0.mca`.
+    pub fn file_name((x, z): (i32, i32)) -> String {
+        format!("r.{}.{}.mca", x.div_euclid(REGION_WIDTH), z.div_euclid(REGION_WIDTH))
+    }
+
+    /// Returns when the chunk at `location` was last saved in seconds since
+    /// the epoch, 0 if it never was.
+    pub fn timestamp(&self, location: (i32, i32)) -> u32 {
+        self.timestamps[index(location)]
+    }
+
+    /// Reads the root tag of the chunk at `location`, `None` if it is not
+    /// saved in this region.
+    pub fn read_chunk(&mut self, location: (i32, i32)) -> Result<Option<Tag>, AnvilErr> {
+        let entry = self.locations[index(location)];
+        let (sector, sectors) = ((entry >> 8) as usize, (entry & 0xff) as usize);
+        if entry == 0 {
+            return Ok(None);
+        }
+        self.file.seek(SeekFrom::Start((sector * SECTOR_LENGTH) as u64))?;
+        let mut prefix = [0; 5];
+        self.file.read_exact(&mut prefix)?;
+        let length = u32::from_be_bytes(prefix[..4].try_into().unwrap()) as usize;
+        let compression = prefix[4];
+        if length == 0 || length + 4 > sectors * SECTOR_LENGTH {
+            return Err(AnvilErr::Malformed(format!("chunk {:?} overflows its sectors", location)));
+        }
+        let data = if compression & EXTERNAL_FLAG != 0 {
+            let name = format!("c.{}.{}.mcc", location.0, location.1);
+            fs::read(self.path.with_file_name(name))?
+        } else {
+            let mut data = vec![0; length - 1];
+            self.file.read_exact(&mut data)?;
+            data
+        };
+        match compression & !EXTERNAL_FLAG {
+            // gzip, zlib and none, told apart again when reading
+            1..=3 => {
+                let (_, tag) = nbt::read_compressed(&data)?;
+                Ok(Some(tag))
+            }
+            compression => Err(AnvilErr::UnknownCompression(compression)),
+        }
+    }
+
+    /// Writes the root tag of the chunk at `location` with zlib, in the first
+    /// free sectors large enough to hold it.
+    pub fn write_chunk(&mut self, location: (i32, i32), tag: &Tag) -> Result<(), AnvilErr> {
+        let data = nbt::write_compressed("", tag, Compression::Zlib)?;
+        let external = self.path.with_file_name(format!("c.{}.{}.mcc", location.0, location.1));
+        let sectors = sectors_for(data.len());
+        let mut chunk = Vec::with_capacity(sectors * SECTOR_LENGTH);
+        if sectors > MAX_SECTORS {
+            fs::write(&external, &data)?;
+            chunk.extend_from_slice(&1u32.to_be_bytes());
+            chunk.push(ZLIB | EXTERNAL_FLAG);
+        } else {
+            if external.exists() {
+                fs::remove_file(&external)?;
+            }
+            chunk.extend_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+            chunk.push(ZLIB);
+            chunk.extend_from_slice(&data);
+        }
+        chunk.resize(chunk.len().div_ceil(SECTOR_LENGTH) * SECTOR_LENGTH, 0);
+
+        let index = index(location);
+        let sectors = chunk.len() / SECTOR_LENGTH;
+        let sector = self.allocate(index, sectors);
+        self.file.seek(SeekFrom::Start((sector * SECTOR_LENGTH) as u64))?;
+        self.file.write_all(&chunk)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_secs() as u32);
+        self.locations[index] = (sector as u32) << 8 | sectors as u32;
+        self.timestamps[index] = timestamp;
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_all(&self.locations[index].to_be_bytes())?;
+        self.file.seek(SeekFrom::Start((SECTOR_LENGTH + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the first sector of a run of `sectors` free ones for the chunk
+    /// at `index`, keeping its sectors if they are enough.
+    fn allocate(&self, index: usize, sectors: usize) -> usize {
+        let (current, current_sectors) = ((self.locations[index] >> 8) as usize, (self.locations[index] & 0xff) as usize);
+        if current != 0 && current_sectors >= sectors {
+            return current;
+        }
+        // the two sectors of the header are always used
+        let mut used = vec![true; 2];
+        for (other, entry) in self.locations.iter().enumerate() {
+            let (start, count) = ((entry >> 8) as usize, (entry & 0xff) as usize);
+            if other == index || *entry == 0 {
+                continue;
+            }
+            if used.len() < start + count {
+                used.resize(start + count, false);
+            }
+            used[start..start + count].iter_mut().for_each(|sector| *sector = true);
+        }
+        let mut free = 0;
+        for (sector, is_used) in used.iter().enumerate() {
+            free = if *is_used { 0 } else { free + 1 };
+            if free == sectors {
+                return sector + 1 - sectors;
+            }
+        }
+        used.len() - free
+    }
+}
+
+/// Returns the sectors a chunk of `length` compressed bytes spans, along with
+/// the 5 bytes of its length and compression.
+fn sectors_for(length: usize) -> usize {
+    (length + 5).div_ceil(SECTOR_LENGTH)
+}
+
+/// Index of a chunk in the header of its region.
+fn index((x, z): (i32, i32)) -> usize {
+    (x.rem_euclid(REGION_WIDTH) + z.rem_euclid(REGION_WIDTH) * REGION_WIDTH) as usize
+}
+
+/// The region files of a world, opened as their chunks are first asked for.
+pub struct RegionStorage {
+    directory: PathBuf,
+    regions: HashMap<(i32, i32), Region>,
+}
+
+impl RegionStorage {
+    /// Reads region files from `directory`, the `region` folder of a world.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Loads the chunk at `location`, `None` if it was never saved or is not
+    /// fully generated.
+    pub fn load_column(&mut self, location: (i32, i32)) -> Result<Option<ChunkColumn>, AnvilErr> {
+        let region = match self.region(location, false)? {
+            Some(region) => region,
+            None => return Ok(None),
+        };
+        match region.read_chunk(location)? {
+            Some(tag) => column_from_tag(tag, location),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves a column in its region file, creating it if needed, and clears
+    /// its dirty flag.
+    pub fn save_column(&mut self, column: &mut ChunkColumn) -> Result<(), AnvilErr> {
+        let tag = column_to_tag(column)?;
+        let region = self.region(column.location(), true)?.expect("regions are created");
+        region.write_chunk(column.location(), &tag)?;
+        column.mark_saved();
+        Ok(())
+    }
+
+    /// Returns the region holding the chunk at `location`, opening it the
+    /// first time. Missing files are created if `create` is set.
+    fn region(&mut self, location: (i32, i32), create: bool) -> Result<Option<&mut Region>, AnvilErr> {
+        let region_location = (location.0.div_euclid(REGION_WIDTH), location.1.div_euclid(REGION_WIDTH));
+        if !self.regions.contains_key(&region_location) {
+            let path = self.directory.join(Region::file_name(location));
+            if !path.exists() {
+                if !create {
+                    return Ok(None);
+                }
+                fs::create_dir_all(&self.directory)?;
+            }
+            self.regions.insert(region_location, Region::open(&path)?);
+        }
+        Ok(self.regions.get_mut(&region_location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biome::Biome;
+    use crate::block::{BlockKind, BlockState};
+    use crate::generator::{Superflat, WorldGenerator};
+    use crate::packet::block_entity::BlockEntityKind;
+    use crate::packet::light::LightKind;
+
+    /// Region (-1, 0) holding chunk (-1, 0), saved with zlib, chunk (-2, 3)
+    /// saved with gzip and chunk (-3, 0) whose generation is not over.
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/fixtures/region");
+
+    #[test]
+    fn region_headers_are_read() {
+        let region = Region::open(&Path::new(FIXTURE).join("r.-1.0.mca")).unwrap();
+        assert_eq!(Region::file_name((-1, 0)), "r.-1.0.mca");
+        assert_eq!(Region::file_name((-33, 31)), "r.-2.0.mca");
+        assert_eq!(region.timestamp((-1, 0)), 1_600_000_000);
+        assert_eq!(region.timestamp((-1, 1)), 0);
+    }
+
+    #[test]
+    fn chunks_are_loaded_from_region_files() {
+        let mut storage = RegionStorage::new(FIXTURE);
+        let column = storage.load_column((-1, 0)).unwrap().unwrap();
+        assert_eq!(column.location(), (-1, 0));
+        assert_eq!(column.primary_bit_mask(), 0b11);
+        assert_eq!(column.block_at(0, 0, 0), BlockKind::Bedrock.default_state());
+        assert_eq!(column.block_at(5, 17, 9), BlockKind::Stone.default_state());
+        assert_eq!(column.block_at(5, 20, 9), BlockKind::GrassBlock.default_state());
+        assert!(column.block_at(5, 21, 9).is_air());
+        let sign = column.block_at(3, 21, 4);
+        assert_eq!(sign.kind(), BlockKind::OakSign);
+        assert_eq!(sign.property("rotation"), Some("4"));
+        match &column.block_entities().get(3, 21, 4).unwrap().kind {
+            BlockEntityKind::Sign(sign) => assert_eq!(sign.text1, r#"{"text":"Hello"}"#),
+            kind => panic!("expected a sign, found {:?}", kind),
+        }
+        assert_eq!(column.world_surface().height(3, 4), 22);
+        assert_eq!(column.motion_blocking().height(0, 0), 21);
+        assert_eq!(column.biomes().get(0, 0, 0), Biome::Forest);
+        assert!(column.is_lit());
+        assert_eq!(column.light().get(LightKind::Sky, 5, 21, 9), 15);
+        assert_eq!(column.light().get(LightKind::Sky, 5, 20, 9), 0);
+
+        let column = storage.load_column((-2, 3)).unwrap().unwrap();
+        assert_eq!(column.block_at(15, 255, 15), BlockKind::Glass.default_state());
+        assert_eq!(column.block_at(1, 0, 0), BlockKind::WhiteWool.default_state());
+        assert_eq!(column.block_at(0, 0, 1), BlockKind::BlackWool.default_state());
+        assert_eq!(column.world_surface().height(15, 15), 256);
+        assert!(!column.is_lit());
+
+        assert!(storage.load_column((-3, 0)).unwrap().is_none());
+        assert!(storage.load_column((-1, 1)).unwrap().is_none());
+        assert!(storage.load_column((0, 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn chunks_of_other_versions_are_errors() {
+        let chunk = |version: i32| {
+            let level = r#"{xPos: 0, zPos: 0, Status: "full", Sections: []}"#;
+            let snbt = format!("{{DataVersion: {}, Level: {}}}", version, level);
+            column_from_tag(nbt::snbt::parse(&snbt).unwrap(), (0, 0))
+        };
+        assert!(chunk(2230).unwrap().is_some());
+        assert!(matches!(chunk(2566), Err(AnvilErr::NewerDataVersion(2566))));
+        assert!(matches!(chunk(1343), Err(AnvilErr::Malformed(_))));
+    }
+
+    #[test]
+    fn chunks_are_saved_and_loaded_again() {
+        let directory = std::env::temp_dir().join(format!("mycraft-regions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::copy(Path::new(FIXTURE).join("r.-1.0.mca"), directory.join("r.-1.0.mca")).unwrap();
+
+        let mut storage = RegionStorage::new(&directory);
+        let mut sign_column = storage.load_column((-1, 0)).unwrap().unwrap();
+        assert!(!sign_column.is_dirty());
+        assert!(sign_column.extra_tags().contains_key("TileTicks"));
+        sign_column.set_block_at(5, 100, 9, BlockKind::Glass.default_state());
+        assert!(sign_column.is_dirty());
+        storage.save_column(&mut sign_column).unwrap();
+        assert!(!sign_column.is_dirty());
+
+        // growing past its sectors moves the chunk after the other ones
+        let mut wool_column = storage.load_column((-2, 3)).unwrap().unwrap();
+        for index in 0..4096 {
+            let state = BlockState::from_id(1 + (index * 7 % 3000) as u16).unwrap();
+            wool_column.set_block_at(index & 15, 32 + (index >> 8), (index >> 4) & 15, state);
+        }
+        storage.save_column(&mut wool_column).unwrap();
+        let mut new_column = Superflat::default().generate((0, 0));
+        storage.save_column(&mut new_column).unwrap();
+        assert!(directory.join("r.0.0.mca").exists());
+
+        let mut storage = RegionStorage::new(&directory);
+        for column in [&sign_column, &wool_column, &new_column].iter() {
+            let loaded = storage.load_column(column.location()).unwrap().unwrap();
+            assert_eq!(column_to_tag(&loaded).unwrap(), column_to_tag(column).unwrap());
+        }
+        assert!(storage.load_column((-3, 0)).unwrap().is_none());
+        let mut region = Region::open(&directory.join("r.-1.0.mca")).unwrap();
+        // entities are kept although the server does not read them
+        let tag = region.read_chunk((-1, 0)).unwrap().unwrap();
+        let entities = match tag.get("Level").and_then(|level| level.get("Entities")) {
+            Some(Tag::List(entities)) => entities.clone(),
+            _ => panic!("no entities"),
+        };
+        assert_eq!(entities.len(), 1);
+        assert_eq!(entities[0].get("id"), Some(&Tag::from("minecraft:armor_stand")));
+        assert!(region.timestamp((-1, 0)) > 1_600_000_000);
+        assert!(region.locations[index((-2, 3))] >> 8 > region.locations[index((-3, 0))] >> 8);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use md5::Md5;
+use rand::{rngs::OsRng, RngCore};
+use rsa::{pkcs8::EncodePublicKey, Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use uuid::Uuid;
+
+use std::convert::TryInto;
+use std::fmt::Debug;
+
+pub const SESSION_SERVER_URL: &str = "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+#[derive(Debug, Clone, Error)]
+pub enum AuthErr {
+    #[error("Could not decrypt the encryption response.")]
+    Decryption,
+    #[error("Shared secret must be 16 bytes long.")]
+    InvalidSecret,
+    #[error("Verify token does not match.")]
+    VerifyTokenMismatch,
+    #[error("Session server request failed: {0}")]
+    SessionServer(String),
+}
+
+/// Identity of a player, as returned by the session server in online mode.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GameProfile {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Vec<ProfileProperty>,
+}
+
+impl GameProfile {
+    /// Creates the profile an offline-mode server assigns to `name`.
+    pub fn offline(name: &str) -> Self {
+        Self {
+            id: offline_uuid(name),
+            name: String::from(name),
+            properties: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+/// Verifies that a client has authenticated with a session server.
+pub trait SessionService: Debug + Send + Sync {
+    /// Returns the profile of `username` if it has joined the server identified by `server_hash`.
+    fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<GameProfile>, AuthErr>;
+}
+
+/// `SessionService` speaking the Mojang session server API.
+// see https://wiki.vg/Protocol_Encryption#Server
+#[derive(Debug)]
+pub struct MojangSessionService {
+    url: String,
+}
+
+impl MojangSessionService {
+    pub fn new() -> Self {
+        Self::with_url(SESSION_SERVER_URL)
+    }
+
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            url: String::from(url),
+        }
+    }
+}
+
+impl Default for MojangSessionService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionService for MojangSessionService {
+    fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<GameProfile>, AuthErr> {
+        let response = ureq::get(&self.url)
+            .query("username", username)
+            .query("serverId", server_hash)
+            .call()
+            .map_err(|e| AuthErr::SessionServer(e.to_string()))?;
+        if response.status() == 204 {
+            return Ok(None);
+        }
+        let profile = response
+            .into_json()
+            .map_err(|e| AuthErr::SessionServer(e.to_string()))?;
+        Ok(Some(profile))
+    }
+}
+
+/// RSA keypair used for the encryption handshake.
+pub struct ServerKey {
+    private: RsaPrivateKey,
+    public_der: Vec<u8>,
+}
+
+impl ServerKey {
+    pub fn generate() -> Self {
+        let private = RsaPrivateKey::new(&mut OsRng, 1024).unwrap();
+        let public_der = RsaPublicKey::from(&private)
+            .to_public_key_der()
+            .unwrap()
+            .into_vec();
+        Self {
+            private,
+            public_der,
+        }
+    }
+
+    /// Returns the public key in the ASN.1 DER format sent in Encryption Request.
+    pub fn public_der(&self) -> &[u8] {
+        &self.public_der
+    }
+
+    /// Decrypts an Encryption Response and returns the shared secret.
+    pub fn decrypt_response(
+        &self,
+        shared_secret: &[u8],
+        verify_token: &[u8],
+        expected_token: &[u8],
+    ) -> Result<[u8; 16], AuthErr> {
+        let token = self.decrypt(verify_token)?;
+        if token != expected_token {
+            return Err(AuthErr::VerifyTokenMismatch);
+        }
+        let secret = self.decrypt(shared_secret)?;
+        secret.as_slice().try_into().map_err(|_| AuthErr::InvalidSecret)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, AuthErr> {
+        self.private
+            .decrypt(Pkcs1v15Encrypt, data)
+            .map_err(|_| AuthErr::Decryption)
+    }
+}
+
+pub fn verify_token() -> [u8; 4] {
+    let mut token = [0; 4];
+    OsRng.fill_bytes(&mut token);
+    token
+}
+
+/// Computes Minecraft's signed hexadecimal SHA-1 digest identifying a login.
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_der: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(server_id.as_bytes());
+    hasher.update(shared_secret);
+    hasher.update(public_der);
+    let mut digest: [u8; 20] = hasher.finalize().into();
+    let negative = digest[0] & 0x80 != 0;
+    if negative {
+        // two's complement
+        let mut carry = true;
+        for byte in digest.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                let (value, overflow) = byte.overflowing_add(1);
+                *byte = value;
+                carry = overflow;
+            }
+        }
+    }
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let hex = hex.trim_start_matches('0');
+    if negative {
+        format!("-{}", hex)
+    } else {
+        String::from(hex)
+    }
+}
+
+/// Computes the UUID of an offline-mode player, the same way as Java's
+/// `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")`.
+pub fn offline_uuid(name: &str) -> Uuid {
+    let digest = Md5::digest(format!("OfflinePlayer:{}", name).as_bytes());
+    uuid::Builder::from_md5_bytes(digest.into()).into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha1_hash(name: &str) -> String {
+        server_hash(name, &[], &[])
+    }
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        assert_eq!(offline_uuid("Notch").to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+        assert_eq!(offline_uuid("jeb_").to_string(), "a762f560-4fce-3236-812a-b80efff0b62b");
+    }
+
+    #[test]
+    fn server_hash_matches_minecraft_digest() {
+        assert_eq!(sha1_hash("Notch"), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+        assert_eq!(sha1_hash("jeb_"), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+        assert_eq!(sha1_hash("simon"), "88e16a1019277b15d58faf0541e11910eb756f6");
+    }
+
+    /// Session server which knows a single login.
+    #[derive(Debug)]
+    struct MockSessionService {
+        username: String,
+        server_hash: String,
+    }
+
+    impl SessionService for MockSessionService {
+        fn has_joined(&self, username: &str, server_hash: &str) -> Result<Option<GameProfile>, AuthErr> {
+            if username != self.username || server_hash != self.server_hash {
+                return Ok(None);
+            }
+            Ok(Some(GameProfile {
+                id: Uuid::parse_str("069a79f444e94726a5befca90e38aaf5").unwrap(),
+                name: String::from(username),
+                properties: Vec::new(),
+            }))
+        }
+    }
+
+    #[test]
+    fn encryption_exchange() {
+        let key = ServerKey::generate();
+        let token = verify_token();
+
+        // client side
+        let public = RsaPublicKey::from(&key.private);
+        let secret = [7u8; 16];
+        let encrypted_secret = public.encrypt(&mut OsRng, Pkcs1v15Encrypt, &secret).unwrap();
+        let encrypted_token = public.encrypt(&mut OsRng, Pkcs1v15Encrypt, &token).unwrap();
+        let client_hash = server_hash("", &secret, key.public_der());
+        let service = MockSessionService {
+            username: String::from("Notch"),
+            server_hash: client_hash,
+        };
+
+        // server side
+        let decrypted = key
+            .decrypt_response(&encrypted_secret, &encrypted_token, &token)
+            .unwrap();
+        assert_eq!(decrypted, secret);
+        let hash = server_hash("", &decrypted, key.public_der());
+        let profile = service.has_joined("Notch", &hash).unwrap().unwrap();
+        assert_eq!(profile.id.to_string(), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert!(service.has_joined("Notch", "bogus").unwrap().is_none());
+
+        let wrong_token = public.encrypt(&mut OsRng, Pkcs1v15Encrypt, &[0u8; 4]).unwrap();
+        assert!(matches!(
+            key.decrypt_response(&encrypted_secret, &wrong_token, &token),
+            Err(AuthErr::VerifyTokenMismatch)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+//! Biomes with their numeric ids, as sent in Chunk Data and saved in region
+//! files. Ids are those of 1.15.
+// see https://minecraft.gamepedia.com/Biome#Biome_IDs
+
+use num_enum::TryFromPrimitive;
+
+use std::convert::TryFrom;
+
+macro_rules! biomes {
+    ($($variant:ident = $id:literal => $name:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+        #[repr(i32)]
+        pub enum Biome {
+            $($variant = $id,)*
+        }
+
+        impl Biome {
+            /// Every biome, by increasing id.
+            pub const ALL: &'static [Biome] = &[$(Biome::$variant,)*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Biome::$variant => $name,)*
+                }
+            }
+
+            /// Returns the biome named `name`, such as `minecraft:plains`.
+            pub fn from_name(name: &str) -> Option<Biome> {
+                match name {
+                    $($name => Some(Biome::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+biomes! {
+    Ocean = 0 => "minecraft:ocean",
+    Plains = 1 => "minecraft:plains",
+    Desert = 2 => "minecraft:desert",
+    Mountains = 3 => "minecraft:mountains",
+    Forest = 4 => "minecraft:forest",
+    Taiga = 5 => "minecraft:taiga",
+    Swamp = 6 => "minecraft:swamp",
+    River = 7 => "minecraft:river",
+    Nether = 8 => "minecraft:nether",
+    TheEnd = 9 => "minecraft:the_end",
+    FrozenOcean = 10 => "minecraft:frozen_ocean",
+    FrozenRiver = 11 => "minecraft:frozen_river",
+    SnowyTundra = 12 => "minecraft:snowy_tundra",
+    SnowyMountains = 13 => "minecraft:snowy_mountains",
+    MushroomFields = 14 => "minecraft:mushroom_fields",
+    MushroomFieldShore = 15 => "minecraft:mushroom_field_shore",
+    Beach = 16 => "minecraft:beach",
+    DesertHills = 17 => "minecraft:desert_hills",
+    WoodedHills = 18 => "minecraft:wooded_hills",
+    TaigaHills = 19 => "minecraft:taiga_hills",
+    MountainEdge = 20 => "minecraft:mountain_edge",
+    Jungle = 21 => "minecraft:jungle",
+    JungleHills = 22 => "minecraft:jungle_hills",
+    JungleEdge = 23 => "minecraft:jungle_edge",
+    DeepOcean = 24 => "minecraft:deep_ocean",
+    StoneShore = 25 => "minecraft:stone_shore",
+    SnowyBeach = 26 => "minecraft:snowy_beach",
+    BirchForest = 27 => "minecraft:birch_forest",
+    BirchForestHills = 28 => "minecraft:birch_forest_hills",
+    DarkForest = 29 => "minecraft:dark_forest",
+    SnowyTaiga = 30 => "minecraft:snowy_taiga",
+    SnowyTaigaHills = 31 => "minecraft:snowy_taiga_hills",
+    GiantTreeTaiga = 32 => "minecraft:giant_tree_taiga",
+    GiantTreeTaigaHills = 33 => "minecraft:giant_tree_taiga_hills",
+    WoodedMountains = 34 => "minecraft:wooded_mountains",
+    Savanna = 35 => "minecraft:savanna",
+    SavannaPlateau = 36 => "minecraft:savanna_plateau",
+    Badlands = 37 => "minecraft:badlands",
+    WoodedBadlandsPlateau = 38 => "minecraft:wooded_badlands_plateau",
+    BadlandsPlateau = 39 => "minecraft:badlands_plateau",
+    SmallEndIslands = 40 => "minecraft:small_end_islands",
+    EndMidlands = 41 => "minecraft:end_midlands",
+    EndHighlands = 42 => "minecraft:end_highlands",
+    EndBarrens = 43 => "minecraft:end_barrens",
+    WarmOcean = 44 => "minecraft:warm_ocean",
+    LukewarmOcean = 45 => "minecraft:lukewarm_ocean",
+    ColdOcean = 46 => "minecraft:cold_ocean",
+    DeepWarmOcean = 47 => "minecraft:deep_warm_ocean",
+    DeepLukewarmOcean = 48 => "minecraft:deep_lukewarm_ocean",
+    DeepColdOcean = 49 => "minecraft:deep_cold_ocean",
+    DeepFrozenOcean = 50 => "minecraft:deep_frozen_ocean",
+    TheVoid = 127 => "minecraft:the_void",
+    SunflowerPlains = 129 => "minecraft:sunflower_plains",
+    DesertLakes = 130 => "minecraft:desert_lakes",
+    GravellyMountains = 131 => "minecraft:gravelly_mountains",
+    FlowerForest = 132 => "minecraft:flower_forest",
+    TaigaMountains = 133 => "minecraft:taiga_mountains",
+    SwampHills = 134 => "minecraft:swamp_hills",
+    IceSpikes = 140 => "minecraft:ice_spikes",
+    ModifiedJungle = 149 => "minecraft:modified_jungle",
+    ModifiedJungleEdge = 151 => "minecraft:modified_jungle_edge",
+    TallBirchForest = 155 => "minecraft:tall_birch_forest",
+    TallBirchHills = 156 => "minecraft:tall_birch_hills",
+    DarkForestHills = 157 => "minecraft:dark_forest_hills",
+    SnowyTaigaMountains = 158 => "minecraft:snowy_taiga_mountains",
+    GiantSpruceTaiga = 160 => "minecraft:giant_spruce_taiga",
+    GiantSpruceTaigaHills = 161 => "minecraft:giant_spruce_taiga_hills",
+    ModifiedGravellyMountains = 162 => "minecraft:modified_gravelly_mountains",
+    ShatteredSavanna = 163 => "minecraft:shattered_savanna",
+    ShatteredSavannaPlateau = 164 => "minecraft:shattered_savanna_plateau",
+    ErodedBadlands = 165 => "minecraft:eroded_badlands",
+    ModifiedWoodedBadlandsPlateau = 166 => "minecraft:modified_wooded_badlands_plateau",
+    ModifiedBadlandsPlateau = 167 => "minecraft:modified_badlands_plateau",
+    BambooJungle = 168 => "minecraft:bamboo_jungle",
+    BambooJungleHills = 169 => "minecraft:bamboo_jungle_hills",
+}
+
+impl Biome {
+    pub fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_id(id: i32) -> Option<Biome> {
+        Biome::try_from(id).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biomes_have_vanilla_ids_and_names() {
+        assert_eq!(Biome::from_id(21), Some(Biome::Jungle));
+        assert_eq!(Biome::from_id(51), None);
+        assert_eq!(Biome::BambooJungleHills.id(), 169);
+        for biome in Biome::ALL.iter() {
+            assert_eq!(Biome::from_name(biome.name()), Some(*biome));
+            assert_eq!(Biome::from_id(biome.id()), Some(*biome));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Blocks and their states, generated by build.rs from the vanilla blocks
+//! report in data/blocks.json and their light and collision from
+//! data/block_physics.json. State ids are those of 1.15.
+// see https://wiki.vg/Data_Generators#Blocks_report
+
+use std::sync::OnceLock;
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+struct BlockInfo {
+    kind: BlockKind,
+    name: &'static str,
+    first_state: u16,
+    default_state: u16,
+    blocks_motion: bool,
+    light_opacity: u8,
+    /// Sorted by name, the states of a block go through the values of the
+    /// last property first.
+    properties: &'static [Property],
+}
+
+struct Property {
+    name: &'static str,
+    values: &'static [&'static str],
+}
+
+impl BlockKind {
+    fn info(self) -> &'static BlockInfo {
+        &BLOCKS[self as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn default_state(self) -> BlockState {
+        BlockState(self.info().default_state)
+    }
+}
+
+/// A block with the values of all its properties, sent as its global id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockState(u16);
+
+impl BlockState {
+    pub const AIR: BlockState = BlockState(0);
+
+    pub fn from_id(id: u16) -> Option<BlockState> {
+        if id < STATE_COUNT {
+            Some(BlockState(id))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the state of the block named `name` with the given properties,
+    /// properties which are not given keep their default value.
+    pub fn from_name(name: &str, properties: &[(&str, &str)]) -> Option<BlockState> {
+        let mut state = BlockKind::from_name(name)?.default_state();
+        for (property, value) in properties.iter() {
+            state = state.with(property, value)?;
+        }
+        Some(state)
+    }
+
+    pub fn id(self) -> u16 {
+        self.0
+    }
+
+    pub fn kind(self) -> BlockKind {
+        self.info().kind
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn is_air(self) -> bool {
+        matches!(self.kind(), BlockKind::Air | BlockKind::CaveAir | BlockKind::VoidAir)
+    }
+
+    /// Returns whether the material of the block stops entities, which is
+    /// what the `MOTION_BLOCKING` heightmap looks at along with fluids.
+    pub fn blocks_motion(self) -> bool {
+        self.physics().blocks_motion
+    }
+
+    /// Returns whether the block holds water or lava.
+    pub fn has_fluid(self) -> bool {
+        self.physics().has_fluid
+    }
+
+    /// Returns the block light level the block gives off.
+    pub fn light_emission(self) -> u8 {
+        self.physics().light_emission
+    }
+
+    /// Returns how much light is lost going through the block, 15 for opaque
+    /// full blocks and 0 for those light goes through freely.
+    pub fn light_opacity(self) -> u8 {
+        self.physics().light_opacity
+    }
+
+    /// Looks up the derived properties of the state, gathering them for
+    /// every state the first time.
+    fn physics(self) -> Physics {
+        static PHYSICS: OnceLock<Vec<Physics>> = OnceLock::new();
+        let physics = PHYSICS.get_or_init(|| {
+            (0..STATE_COUNT)
+                .map(|id| {
+                    let state = BlockState(id);
+                    let info = state.info();
+                    Physics {
+                        blocks_motion: info.blocks_motion,
+                        has_fluid: state.compute_has_fluid(),
+                        light_emission: LIGHT_EMISSION[id as usize],
+                        light_opacity: info.light_opacity,
+                    }
+                })
+                .collect()
+        });
+        physics[self.0 as usize]
+    }
+
+    fn compute_has_fluid(self) -> bool {
+        use BlockKind::*;
+        matches!(
+            self.kind(),
+            Water | Lava | BubbleColumn | Seagrass | TallSeagrass | Kelp | KelpPlant
+        ) || self.property("waterlogged") == Some("true")
+    }
+
+    /// Returns the value of every property of the block.
+    pub fn properties(self) -> Vec<(&'static str, &'static str)> {
+        let info = self.info();
+        let mut offset = self.0 - info.first_state;
+        let mut properties = Vec::with_capacity(info.properties.len());
+        for property in info.properties.iter().rev() {
+            let count = property.values.len() as u16;
+            properties.push((property.name, property.values[(offset % count) as usize]));
+            offset /= count;
+        }
+        properties.reverse();
+        properties
+    }
+
+    pub fn property(self, name: &str) -> Option<&'static str> {
+        self.properties()
+            .into_iter()
+            .find(|(property, _)| *property == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns this state with `name` set to `value`, `None` if the block has
+    /// no such property or value.
+    pub fn with(self, name: &str, value: &str) -> Option<BlockState> {
+        let info = self.info();
+        let mut stride = 1;
+        for property in info.properties.iter().rev() {
+            let count = property.values.len() as u16;
+            if property.name == name {
+                let new = property.values.iter().position(|v| *v == value)? as u16;
+                let old = (self.0 - info.first_state) / stride % count;
+                return Some(BlockState(self.0 - old * stride + new * stride));
+            }
+            stride *= count;
+        }
+        None
+    }
+
+    fn info(self) -> &'static BlockInfo {
+        let index = BLOCKS.partition_point(|block| block.first_state <= self.0);
+        &BLOCKS[index - 1]
+    }
+}
+
+/// Properties of a state which follow from its block and property values.
+#[derive(Clone, Copy)]
+struct Physics {
+    blocks_motion: bool,
+    has_fluid: bool,
+    light_emission: u8,
+    light_opacity: u8,
+}
+
+impl Default for BlockState {
+    fn default() -> Self {
+        BlockState::AIR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_have_vanilla_ids() {
+        assert_eq!(BlockKind::Stone.default_state().id(), 1);
+        assert_eq!(BlockKind::GrassBlock.default_state().id(), 9);
+        assert_eq!(BlockKind::StoneBricks.default_state().id(), 4481);
+        assert_eq!(BlockState::from_id(STATE_COUNT - 1).unwrap().kind(), BlockKind::HoneycombBlock);
+        assert_eq!(BlockState::from_id(STATE_COUNT), None);
+        assert!(BlockState::from_id(9130).unwrap().is_air());
+        assert!(BlockKind::Stone.default_state().blocks_motion());
+        assert!(!BlockKind::Torch.default_state().blocks_motion());
+        assert!(BlockKind::MushroomStem.default_state().blocks_motion());
+        assert!(BlockKind::PistonHead.default_state().blocks_motion());
+        assert!(BlockKind::Water.default_state().has_fluid());
+        assert!(BlockKind::Conduit.default_state().has_fluid());
+        assert_eq!(BlockKind::Torch.default_state().light_emission(), 14);
+        assert_eq!(BlockKind::Furnace.default_state().light_emission(), 0);
+        assert_eq!(BlockKind::Furnace.default_state().with("lit", "true").unwrap().light_emission(), 13);
+        assert_eq!(BlockKind::Stone.default_state().light_opacity(), 15);
+        assert_eq!(BlockKind::Glass.default_state().light_opacity(), 0);
+        assert_eq!(BlockKind::OakLeaves.default_state().light_opacity(), 1);
+    }
+
+    #[test]
+    fn lookup_by_name_and_properties() {
+        let stairs = BlockState::from_name(
+            "minecraft:oak_stairs",
+            &[("facing", "east"), ("half", "top"), ("waterlogged", "true")],
+        )
+        .unwrap();
+        assert_eq!(stairs.name(), "minecraft:oak_stairs");
+        assert_eq!(
+            stairs.properties(),
+            vec![("facing", "east"), ("half", "top"), ("shape", "straight"), ("waterlogged", "true")]
+        );
+        assert_eq!(stairs.with("half", "bottom").unwrap().property("half"), Some("bottom"));
+        assert_eq!(stairs.with("half", "bottom").unwrap().property("facing"), Some("east"));
+        assert_eq!(BlockState::from_name("minecraft:oak_stairs", &[("open", "true")]), None);
+        assert_eq!(BlockState::from_name("minecraft:oak_stairs", &[("half", "upper")]), None);
+        assert_eq!(BlockState::from_name("minecraft:unknown", &[]), None);
+        assert_eq!(BlockState::from_name("minecraft:stone", &[]), Some(BlockState(1)));
+    }
+
+    #[test]
+    fn every_light_source_of_1_15_2_shines() {
+        let lit = |state: BlockState| state.property("lit") == Some("true");
+        let pickles = |state: BlockState| {
+            let pickles: u8 = state.property("pickles").unwrap().parse().unwrap();
+            if state.property("waterlogged") == Some("true") {
+                3 + 3 * pickles
+            } else {
+                0
+            }
+        };
+        let sources: &[(&str, &dyn Fn(BlockState) -> u8)] = &[
+            ("minecraft:lava", &|_| 15),
+            ("minecraft:brown_mushroom", &|_| 1),
+            ("minecraft:torch", &|_| 14),
+            ("minecraft:wall_torch", &|_| 14),
+            ("minecraft:fire", &|_| 15),
+            ("minecraft:furnace", &|state| if lit(state) { 13 } else { 0 }),
+            ("minecraft:redstone_ore", &|state| if lit(state) { 9 } else { 0 }),
+            ("minecraft:redstone_torch", &|state| if lit(state) { 7 } else { 0 }),
+            ("minecraft:redstone_wall_torch", &|state| if lit(state) { 7 } else { 0 }),
+            ("minecraft:glowstone", &|_| 15),
+            ("minecraft:nether_portal", &|_| 11),
+            ("minecraft:jack_o_lantern", &|_| 15),
+            ("minecraft:brewing_stand", &|_| 1),
+            ("minecraft:end_portal", &|_| 15),
+            ("minecraft:end_portal_frame", &|_| 1),
+            ("minecraft:dragon_egg", &|_| 1),
+            ("minecraft:redstone_lamp", &|state| if lit(state) { 15 } else { 0 }),
+            ("minecraft:ender_chest", &|_| 7),
+            ("minecraft:beacon", &|_| 15),
+            ("minecraft:sea_lantern", &|_| 15),
+            ("minecraft:end_rod", &|_| 14),
+            ("minecraft:end_gateway", &|_| 15),
+            ("minecraft:magma_block", &|_| 3),
+            ("minecraft:sea_pickle", &pickles),
+            ("minecraft:conduit", &|_| 15),
+            ("minecraft:smoker", &|state| if lit(state) { 13 } else { 0 }),
+            ("minecraft:blast_furnace", &|state| if lit(state) { 13 } else { 0 }),
+            ("minecraft:lantern", &|_| 15),
+            ("minecraft:campfire", &|state| if lit(state) { 15 } else { 0 }),
+        ];
+        for id in 0..STATE_COUNT {
+            let state = BlockState(id);
+            let expected = match sources.iter().find(|(name, _)| *name == state.name()) {
+                Some((_, emission)) => emission(state),
+                None => 0,
+            };
+            assert_eq!(state.light_emission(), expected, "{} {:?}", state.name(), state.properties());
+        }
+        for (name, _) in sources.iter() {
+            assert!(BlockKind::from_name(name).is_some(), "{} is not a block", name);
+        }
+    }
+}
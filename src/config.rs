@@ -0,0 +1,62 @@
+use crate::auth::{MojangSessionService, SessionService};
+use crate::generator::{Superflat, WorldGenerator};
+use crate::packet::{codec::DEFAULT_MAX_PACKET_LENGTH, reader::DEFAULT_MAX_STRING_LENGTH};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Server-wide settings shared by every connection.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub address: String,
+    pub motd: String,
+    pub max_players: u32,
+    /// Packets at least this large are compressed, `None` disables compression.
+    pub compression_threshold: Option<usize>,
+    /// Authenticates players with the session server and encrypts their connections.
+    pub online_mode: bool,
+    /// Session server players authenticate with in online mode.
+    pub sessions: Arc<dyn SessionService>,
+    /// Connections sending a larger packet, in bytes, are closed.
+    pub max_packet_length: usize,
+    /// Connections sending a longer string, in characters, are closed.
+    pub max_string_length: usize,
+    /// Chunks around players which are sent to them, in each direction.
+    pub view_distance: u8,
+    /// Folder of the world, chunks saved in its `region` folder are loaded
+    /// rather than generated.
+    pub world_directory: PathBuf,
+    /// How often chunks which changed are saved, they also are on shutdown.
+    pub autosave_interval: Duration,
+    /// How often chunks no player looks at are saved and unloaded.
+    pub unload_interval: Duration,
+    /// Creates the chunks which are not saved in the world folder.
+    pub generator: Arc<dyn WorldGenerator>,
+}
+
+impl ServerConfig {
+    pub fn new() -> Self {
+        Self {
+            address: String::from("0.0.0.0:7781"),
+            motd: String::from("A Mycraft Server"),
+            max_players: 20,
+            compression_threshold: Some(256),
+            online_mode: false,
+            sessions: Arc::new(MojangSessionService::new()),
+            max_packet_length: DEFAULT_MAX_PACKET_LENGTH,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            view_distance: 10,
+            world_directory: PathBuf::from("world"),
+            autosave_interval: Duration::from_secs(300),
+            unload_interval: Duration::from_secs(30),
+            generator: Arc::new(Superflat::default()),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,39 @@
+//! World generators, creating the chunks which were never saved.
+
+mod noise;
+mod superflat;
+mod terrain;
+
+pub use superflat::Superflat;
+pub use terrain::{Terrain, SEA_LEVEL};
+
+use thiserror::Error;
+
+use std::fmt::Debug;
+
+use crate::packet::chunk::ChunkColumn;
+
+/// Creates the chunks of a world, servers pick one or write their own.
+pub trait WorldGenerator: Debug + Send + Sync {
+    /// Generates the column at `location`. Generators may light it, it is lit
+    /// by the server otherwise.
+    fn generate(&self, location: (i32, i32)) -> ChunkColumn;
+
+    /// Returns the level type sent in Join Game, `flat` lowering the horizon
+    /// of clients.
+    fn level_type(&self) -> &str {
+        "default"
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GeneratorErr {
+    #[error("Unknown block {0}.")]
+    UnknownBlock(String),
+    #[error("Unknown biome {0}.")]
+    UnknownBiome(String),
+    #[error("Invalid layer {0}.")]
+    InvalidLayer(String),
+    #[error("Layers are {0} blocks high, higher than the world.")]
+    TooHigh(usize),
+}
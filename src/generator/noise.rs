@@ -0,0 +1,181 @@
+//! Seeded gradient noise for terrain. Everything here is computed from the
+//! seed alone so that a seed always gives the same world, whatever the
+//! platform or the version of the `rand` crate.
+// see https://mrl.cs.nyu.edu/~perlin/noise/
+
+/// The SplitMix64 generator, small and good enough to shuffle permutations
+/// and scatter blocks.
+// see https://prng.di.unimi.it/splitmix64.c
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Hashes a block position with a seed, for choices made block by block.
+pub fn hash_position(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let position = (x as u32 as u64) << 32 | (z as u32 as u64);
+    let mut random = SplitMix64::new(seed ^ position ^ (y as u64).rotate_left(48));
+    random.next_u64()
+}
+
+/// Improved Perlin noise, from -1 to 1 and 0 on every integer coordinate.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    /// A shuffle of 0 to 255, twice so that indices do not need wrapping.
+    permutation: Vec<u8>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut random = SplitMix64::new(seed);
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        for i in (1..permutation.len()).rev() {
+            let j = (random.next_u64() % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+        permutation.extend_from_within(..);
+        Self { permutation }
+    }
+
+    pub fn get(&self, x: f64, y: f64, z: f64) -> f64 {
+        let p = &self.permutation;
+        let (xi, yi, zi) = (lattice(x), lattice(y), lattice(z));
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = p[xi] as usize + yi;
+        let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+        let b = p[xi + 1] as usize + yi;
+        let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(u, grad(p[ab], x, y - 1.0, z), grad(p[bb], x - 1.0, y - 1.0, z)),
+            ),
+            lerp(
+                v,
+                lerp(u, grad(p[aa + 1], x, y, z - 1.0), grad(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+/// Noise summed over octaves of doubling frequencies and halving amplitudes,
+/// giving both large features and small details.
+#[derive(Debug, Clone)]
+pub struct Octaves {
+    octaves: Vec<Perlin>,
+}
+
+impl Octaves {
+    /// Each octave is seeded with the next value of the generator seeded
+    /// with `seed`.
+    pub fn new(seed: u64, count: usize) -> Self {
+        let mut random = SplitMix64::new(seed);
+        Self {
+            octaves: (0..count).map(|_| Perlin::new(random.next_u64())).collect(),
+        }
+    }
+
+    /// Returns the noise from -1 to 1, with features about one unit wide.
+    pub fn get(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (mut sum, mut total, mut scale) = (0.0, 0.0, 1.0);
+        for octave in self.octaves.iter() {
+            sum += octave.get(x / scale, y / scale, z / scale) * scale;
+            total += scale;
+            scale /= 2.0;
+        }
+        sum / total
+    }
+
+    /// Returns the noise of a horizontal plane, for values which only vary
+    /// with `x` and `z`.
+    pub fn get_2d(&self, x: f64, z: f64) -> f64 {
+        // off the lattice, the first octave would be 0 on every integer y
+        self.get(x, 0.5, z)
+    }
+}
+
+/// Index of the cell of `t` in the permutation.
+fn lattice(t: f64) -> usize {
+    (t.floor() as i64 & 255) as usize
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product of the offset with one of 12 gradients picked by `hash`.
+fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perlin_noise_is_seeded() {
+        let (a, b) = (Perlin::new(42), Perlin::new(42));
+        let other = Perlin::new(43);
+        let mut differs = false;
+        for i in 0..1000 {
+            let (x, y, z) = (i as f64 * 0.37 - 150.0, i as f64 * 0.11, i as f64 * -0.53);
+            let value = a.get(x, y, z);
+            assert!((-1.0..=1.0).contains(&value));
+            assert_eq!(value, b.get(x, y, z));
+            differs |= value != other.get(x, y, z);
+        }
+        assert!(differs);
+        assert_eq!(a.get(-3.0, 7.0, 12.0), 0.0);
+        assert_eq!(a.get(0.5, 1.25, -2.75), 0.047739505767822266);
+    }
+
+    #[test]
+    fn octaves_stay_within_bounds() {
+        let octaves = Octaves::new(7, 5);
+        for i in 0..1000 {
+            let value = octaves.get_2d(i as f64 * 0.21, i as f64 * -0.13);
+            assert!((-1.0..=1.0).contains(&value));
+        }
+        assert_eq!(octaves.get_2d(10.3, -4.1), -0.03992112375741997);
+        // the first outputs of the reference implementation
+        assert_eq!(SplitMix64::new(0).next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(SplitMix64::new(1).next_u64(), 0x910a_2dec_8902_5cc1);
+        assert_eq!(hash_position(1, 2, 3, 4), 0x206f_ec76_18cb_cfad);
+        assert_ne!(hash_position(1, 0, 0, 0), hash_position(1, 0, 1, 0));
+    }
+}
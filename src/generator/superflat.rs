@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use crate::biome::Biome;
+use crate::block::{BlockKind, BlockState};
+use crate::generator::{GeneratorErr, WorldGenerator};
+use crate::packet::chunk::{Biomes, ChunkColumn, SECTION_COUNT};
+
+/// A flat world of layers of blocks, the same in every column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Superflat {
+    /// From the bottom up, how many blocks high each layer is.
+    layers: Vec<(usize, BlockState)>,
+    biome: Biome,
+}
+
+impl Superflat {
+    pub fn new(layers: Vec<(usize, BlockState)>, biome: Biome) -> Result<Self, GeneratorErr> {
+        // counts come from presets, their sum may not fit
+        let height = layers
+            .iter()
+            .try_fold(0usize, |height, (count, _)| height.checked_add(*count))
+            .unwrap_or(usize::MAX);
+        if height > SECTION_COUNT * 16 {
+            return Err(GeneratorErr::TooHigh(height));
+        }
+        Ok(Self { layers, biome })
+    }
+
+    pub fn layers(&self) -> &[(usize, BlockState)] {
+        &self.layers
+    }
+
+    pub fn biome(&self) -> Biome {
+        self.biome
+    }
+}
+
+/// Adds the `minecraft:` namespace to names which have none.
+fn namespaced(name: &str) -> String {
+    if name.contains(':') {
+        name.to_owned()
+    } else {
+        format!("minecraft:{}", name)
+    }
+}
+
+fn parse_layer(layer: &str) -> Result<(usize, BlockState), GeneratorErr> {
+    let (count, name) = match layer.split_once('*') {
+        Some((count, name)) => {
+            let count = count
+                .trim()
+                .parse()
+                .map_err(|_| GeneratorErr::InvalidLayer(layer.to_owned()))?;
+            (count, name.trim())
+        }
+        None => (1, layer),
+    };
+    let name = namespaced(name);
+    let kind = BlockKind::from_name(&name).ok_or(GeneratorErr::UnknownBlock(name))?;
+    Ok((count, kind.default_state()))
+}
+
+/// Parses layers the way vanilla presets write them, from the bottom up and
+/// optionally followed by a biome, such as `bedrock,2*dirt,grass_block;plains`.
+impl FromStr for Superflat {
+    type Err = GeneratorErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (layers, biome) = match s.split_once(';') {
+            Some((layers, biome)) => {
+                let name = namespaced(biome.trim());
+                (layers, Biome::from_name(&name).ok_or(GeneratorErr::UnknownBiome(name))?)
+            }
+            None => (s, Biome::Plains),
+        };
+        let layers = layers
+            .split(',')
+            .map(|layer| parse_layer(layer.trim()))
+            .collect::<Result<_, _>>()?;
+        Self::new(layers, biome)
+    }
+}
+
+impl Default for Superflat {
+    fn default() -> Self {
+        Self {
+            layers: vec![
+                (1, BlockKind::Bedrock.default_state()),
+                (2, BlockKind::Dirt.default_state()),
+                (1, BlockKind::GrassBlock.default_state()),
+            ],
+            biome: Biome::Plains,
+        }
+    }
+}
+
+impl WorldGenerator for Superflat {
+    fn generate(&self, location: (i32, i32)) -> ChunkColumn {
+        let mut column = ChunkColumn::empty(location);
+        let mut y = 0;
+        for (count, block) in self.layers.iter() {
+            for _ in 0..*count {
+                for x in 0..16 {
+                    for z in 0..16 {
+                        column.set_block_at(x, y, z, *block);
+                    }
+                }
+                y += 1;
+            }
+        }
+        *column.biomes_mut() = Biomes::filled(self.biome);
+        column
+    }
+
+    fn level_type(&self) -> &str {
+        "flat"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_parsed() {
+        let superflat: Superflat = "minecraft:bedrock, 3*stone,dirt;desert".parse().unwrap();
+        let stone = BlockKind::Stone.default_state();
+        assert_eq!(superflat.layers()[1], (3, stone));
+        assert_eq!(superflat.biome(), Biome::Desert);
+        assert_eq!("bedrock,2*dirt,grass_block".parse::<Superflat>().unwrap(), Superflat::default());
+
+        assert!(matches!("bedrock,dirt*2".parse::<Superflat>(), Err(GeneratorErr::InvalidLayer(_))));
+        assert!(matches!("bedrock,cheese".parse::<Superflat>(), Err(GeneratorErr::UnknownBlock(_))));
+        assert!(matches!("bedrock;moon".parse::<Superflat>(), Err(GeneratorErr::UnknownBiome(_))));
+        assert!(matches!("200*stone,100*dirt".parse::<Superflat>(), Err(GeneratorErr::TooHigh(300))));
+        let overflowing = format!("{}*stone,dirt", usize::MAX);
+        assert!(matches!(overflowing.parse::<Superflat>(), Err(GeneratorErr::TooHigh(usize::MAX))));
+    }
+
+    #[test]
+    fn whole_columns_are_filled() {
+        let superflat: Superflat = "bedrock,2*dirt,grass_block;forest".parse().unwrap();
+        let column = superflat.generate((-4, 7));
+        assert_eq!(column.location(), (-4, 7));
+        for &(x, z) in [(0, 0), (15, 15), (0, 15), (7, 3)].iter() {
+            assert_eq!(column.block_at(x, 0, z), BlockKind::Bedrock.default_state());
+            assert_eq!(column.block_at(x, 2, z), BlockKind::Dirt.default_state());
+            assert_eq!(column.block_at(x, 3, z), BlockKind::GrassBlock.default_state());
+            assert!(column.block_at(x, 4, z).is_air());
+            assert_eq!(column.motion_blocking().height(x, z), 4);
+        }
+        assert_eq!(column.biomes().get(15, 255, 15), Biome::Forest);
+        assert!(column.is_dirty());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::biome::Biome;
+use crate::block::{BlockKind, BlockState};
+use crate::generator::noise::{hash_position, Octaves, Perlin, SplitMix64};
+use crate::generator::WorldGenerator;
+use crate::packet::chunk::{ChunkColumn, SECTION_COUNT};
+
+/// Water fills everything below and at this height.
+pub const SEA_LEVEL: usize = 63;
+
+/// Tunnels are where both cave noises are this close to 0, a higher value
+/// makes wider and more frequent caves.
+const CAVE_THRESHOLD: f64 = 0.004;
+
+/// Hills, mountains and oceans of noise, with biomes picked from their height
+/// and climate. A seed always gives the same world.
+#[derive(Debug, Clone)]
+pub struct Terrain {
+    seed: i64,
+    height: Octaves,
+    /// How far the height goes from the sea level, low in plains and high
+    /// in mountains.
+    roughness: Octaves,
+    temperature: Octaves,
+    humidity: Octaves,
+    caves: (Perlin, Perlin),
+}
+
+impl Terrain {
+    pub fn new(seed: i64) -> Self {
+        let mut random = SplitMix64::new(seed as u64);
+        Self {
+            seed,
+            height: Octaves::new(random.next_u64(), 6),
+            roughness: Octaves::new(random.next_u64(), 2),
+            temperature: Octaves::new(random.next_u64(), 3),
+            humidity: Octaves::new(random.next_u64(), 3),
+            caves: (Perlin::new(random.next_u64()), Perlin::new(random.next_u64())),
+        }
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    /// Returns the height of the top block of the world column at `x` and `z`.
+    pub fn height_at(&self, x: i32, z: i32) -> usize {
+        let (x, z) = (x as f64, z as f64);
+        let roughness = (self.roughness.get_2d(x / 512.0, z / 512.0) + 1.0) / 2.0;
+        let amplitude = 48.0 + 160.0 * roughness * roughness;
+        let height = SEA_LEVEL as f64 + 6.0 + self.height.get_2d(x / 256.0, z / 256.0) * amplitude;
+        height.clamp(8.0, (SECTION_COUNT * 16 - 16) as f64) as usize
+    }
+
+    /// Returns the biome of the world column at `x` and `z`, which is
+    /// `height` blocks high.
+    pub fn biome_at(&self, x: i32, z: i32, height: usize) -> Biome {
+        let (x, z) = (x as f64, z as f64);
+        let temperature = self.temperature.get_2d(x / 1024.0, z / 1024.0);
+        let humidity = self.humidity.get_2d(x / 1024.0, z / 1024.0);
+        let cold = temperature < -0.2;
+        if height + 4 < SEA_LEVEL {
+            if cold {
+                Biome::FrozenOcean
+            } else {
+                Biome::Ocean
+            }
+        } else if height <= SEA_LEVEL + 2 {
+            if cold {
+                Biome::SnowyBeach
+            } else if temperature > 0.2 && humidity < 0.0 {
+                Biome::Desert
+            } else {
+                Biome::Beach
+            }
+        } else if height > SEA_LEVEL + 32 {
+            if cold {
+                Biome::SnowyMountains
+            } else {
+                Biome::Mountains
+            }
+        } else if cold {
+            Biome::SnowyTundra
+        } else if temperature < 0.0 {
+            Biome::Taiga
+        } else if temperature > 0.2 && humidity < 0.0 {
+            Biome::Desert
+        } else if humidity > 0.1 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    /// Returns whether the block at the given world position is carved out
+    /// by a cave.
+    pub fn is_cave(&self, x: i32, y: usize, z: i32) -> bool {
+        let (x, y, z) = (x as f64 / 48.0, y as f64 / 24.0, z as f64 / 48.0);
+        let (a, b) = (self.caves.0.get(x, y, z), self.caves.1.get(x, y, z));
+        a * a + b * b < CAVE_THRESHOLD
+    }
+
+    fn is_bedrock(&self, x: i32, y: usize, z: i32) -> bool {
+        // bedrock gets sparser up to y 4
+        y == 0 || (y < 5 && hash_position(self.seed as u64, x, y as i32, z) % 5 >= y as u64)
+    }
+
+    /// Fills the blocks of the world column at `x` and `z`, `local_x` and
+    /// `local_z` within `column`.
+    fn fill(
+        &self,
+        column: &mut ChunkColumn,
+        (local_x, local_z): (usize, usize),
+        (x, z): (i32, i32),
+        biome: Biome,
+    ) {
+        let height = self.height_at(x, z);
+        let (top, filler) = surface(biome, height);
+        let frozen = matches!(biome, Biome::FrozenOcean | Biome::SnowyBeach);
+        // caves stay away from the ground under water which would flood them
+        let highest_cave = if height > SEA_LEVEL + 2 { height } else { height - 6 };
+        for y in 0..=height.max(SEA_LEVEL) {
+            let block = if self.is_bedrock(x, y, z) {
+                BlockKind::Bedrock.default_state()
+            } else if y <= highest_cave && y >= 5 && self.is_cave(x, y, z) {
+                BlockKind::CaveAir.default_state()
+            } else if y + 3 < height {
+                BlockKind::Stone.default_state()
+            } else if y < height {
+                filler
+            } else if y == height {
+                top
+            } else if y == SEA_LEVEL && frozen {
+                BlockKind::Ice.default_state()
+            } else {
+                BlockKind::Water.default_state()
+            };
+            column.set_block_at(local_x, y, local_z, block);
+        }
+        let snowy = matches!(biome, Biome::SnowyTundra | Biome::SnowyMountains | Biome::SnowyBeach);
+        if snowy && height >= SEA_LEVEL && column.block_at(local_x, height, local_z) == top {
+            column.set_block_at(local_x, height + 1, local_z, BlockKind::Snow.default_state());
+        }
+    }
+}
+
+/// Returns the top block of the ground in `biome` and the block of the few
+/// layers below it.
+fn surface(biome: Biome, height: usize) -> (BlockState, BlockState) {
+    let grass = BlockKind::GrassBlock.default_state();
+    let dirt = BlockKind::Dirt.default_state();
+    let sand = BlockKind::Sand.default_state();
+    match biome {
+        Biome::Desert | Biome::Beach | Biome::SnowyBeach => (sand, sand),
+        Biome::Ocean | Biome::FrozenOcean => {
+            let gravel = BlockKind::Gravel.default_state();
+            (gravel, gravel)
+        }
+        Biome::Mountains | Biome::SnowyMountains if height > SEA_LEVEL + 60 => {
+            let stone = BlockKind::Stone.default_state();
+            (stone, stone)
+        }
+        Biome::SnowyTundra | Biome::SnowyMountains => (grass.with("snowy", "true").unwrap_or(grass), dirt),
+        _ => (grass, dirt),
+    }
+}
+
+impl WorldGenerator for Terrain {
+    fn generate(&self, location: (i32, i32)) -> ChunkColumn {
+        let mut column = ChunkColumn::empty(location);
+        let (base_x, base_z) = (location.0 * 16, location.1 * 16);
+        // clients blend biomes by cubes of 4x4x4 blocks, blocks follow the
+        // biome of their cube
+        for cube_x in 0..4 {
+            for cube_z in 0..4 {
+                let (x, z) = (base_x + cube_x as i32 * 4 + 2, base_z + cube_z as i32 * 4 + 2);
+                let biome = self.biome_at(x, z, self.height_at(x, z));
+                for y in (0..SECTION_COUNT * 16).step_by(4) {
+                    column.biomes_mut().set(cube_x * 4, y, cube_z * 4, biome);
+                }
+                for local_x in cube_x * 4..cube_x * 4 + 4 {
+                    for local_z in cube_z * 4..cube_z * 4 + 4 {
+                        let world = (base_x + local_x as i32, base_z + local_z as i32);
+                        self.fill(&mut column, (local_x, local_z), world, biome);
+                    }
+                }
+            }
+        }
+        column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anvil::column_to_tag;
+
+    #[test]
+    fn seeds_always_give_the_same_terrain() {
+        let (terrain, again, other) = (Terrain::new(1234), Terrain::new(1234), Terrain::new(-1234));
+        for &location in [(0, 0), (3, -7), (-120, 45)].iter() {
+            let column = column_to_tag(&terrain.generate(location)).unwrap();
+            assert_eq!(column, column_to_tag(&again.generate(location)).unwrap());
+            assert_ne!(column, column_to_tag(&other.generate(location)).unwrap());
+        }
+    }
+
+    #[test]
+    fn seeds_give_the_worlds_they_always_gave() {
+        // changing any of these values changes the worlds of existing seeds
+        let terrain = Terrain::new(1234);
+        assert_eq!(terrain.height_at(0, 0), 55);
+        assert_eq!(terrain.biome_at(0, 0, 55), Biome::FrozenOcean);
+        assert_eq!(terrain.height_at(1000, -1000), 60);
+        assert_eq!(terrain.biome_at(1000, -1000, 60), Biome::SnowyBeach);
+        assert!(terrain.is_cave(1000, 30, -1000));
+        assert_eq!(terrain.height_at(-5000, 300), 96);
+        assert_eq!(terrain.biome_at(-5000, 300, 96), Biome::Mountains);
+        assert!(!terrain.is_cave(-5000, 30, 300));
+    }
+
+    #[test]
+    fn terrain_is_layered_under_the_sea_level() {
+        let terrain = Terrain::new(42);
+        let (mut land, mut sea, mut caves) = (false, false, false);
+        for index in 0..16 {
+            let location = ((index % 4 - 2) * 20, (index / 4 - 2) * 20);
+            let column = terrain.generate(location);
+            for x in 0..16 {
+                for z in 0..16 {
+                    let (world_x, world_z) = (location.0 * 16 + x as i32, location.1 * 16 + z as i32);
+                    let height = terrain.height_at(world_x, world_z);
+                    assert_eq!(column.block_at(x, 0, z), BlockKind::Bedrock.default_state());
+                    assert!(!column.block_at(x, SEA_LEVEL, z).is_air() || terrain.is_cave(world_x, SEA_LEVEL, world_z));
+                    assert!(column.block_at(x, height.max(SEA_LEVEL) + 2, z).is_air());
+                    caves |= (5..height).any(|y| column.block_at(x, y, z).kind() == BlockKind::CaveAir);
+                    if height < SEA_LEVEL {
+                        sea = true;
+                        let kind = column.block_at(x, SEA_LEVEL, z).kind();
+                        assert!(kind == BlockKind::Water || kind == BlockKind::Ice);
+                    } else if !terrain.is_cave(world_x, height, world_z) {
+                        land = true;
+                        let (top, _) = surface(column.biomes().get(x, height, z), height);
+                        assert_eq!(column.block_at(x, height, z), top);
+                    }
+                }
+            }
+        }
+        assert!(land && sea && caves);
+    }
+}
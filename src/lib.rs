@@ -1 +1,9 @@
+pub mod anvil;
+pub mod auth;
+pub mod biome;
+pub mod block;
+pub mod config;
+pub mod generator;
+pub mod nbt;
 pub mod packet;
+pub mod world;
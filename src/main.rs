@@ -1,48 +1,146 @@
-use async_std::{io::BufWriter, net::TcpListener, net::TcpStream, prelude::*, task};
+use async_std::{net::TcpListener, net::TcpStream, prelude::*, task};
 
 #[allow(unused_imports)]
-use color_eyre::{eyre::Report, eyre::WrapErr, Section};
+use color_eyre::{eyre::eyre, eyre::Report, eyre::WrapErr, Section};
 
+use futures::SinkExt;
+
+use std::collections::HashSet;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use mycraft::anvil::RegionStorage;
+use mycraft::auth::{self, GameProfile, ServerKey};
+use mycraft::config::ServerConfig;
 use mycraft::packet::{
-    builder::PacketBuilder,
-    codec::{Framed, McCodec},
-    reader::McBytesReader,
-    chunk::{ChunkPacket, ChunkColumn},
+    cipher::{CipherStream, Encryption},
+    codec::{Compression, Frame, Framed, McCodec},
+    reader::{McBytesErr, McBytesReader},
+    handshaking::serverbound::{Handshake, ServerboundHandshaking},
+    login::{clientbound as login_cb, serverbound::{EncryptionResponse, ServerboundLogin}},
+    play::{clientbound as play_cb, serverbound::ServerboundPlay},
+    status::{clientbound::Pong, serverbound::ServerboundStatus, PlayerSample, StatusResponse},
+    types::{Clientbound, Position, RawBytes, VarInt},
+    version::ProtocolVersion,
+    ProtocolState,
 };
+use mycraft::world::{view_change, World};
 
 fn main() -> Result<(), Report> {
     color_eyre::install()?;
-    task::block_on(accept_loop());
+    let server = Arc::new(Server::new(ServerConfig::new()));
+    let shutdown = server.clone();
+    ctrlc::set_handler(move || {
+        println!("Saving the world before stopping.");
+        shutdown.save();
+        std::process::exit(0);
+    })?;
+    task::spawn(autosave_loop(server.clone()));
+    task::spawn(unload_loop(server.clone()));
+    task::block_on(accept_loop(server));
     Ok(())
 }
 
-async fn accept_loop() {
-    let listener = TcpListener::bind("0.0.0.0:7781").await.unwrap();
+async fn accept_loop(server: Arc<Server>) {
+    let listener = TcpListener::bind(&server.config.address).await.unwrap();
     let mut incoming = listener.incoming();
 
     while let Some(stream) = incoming.next().await {
         let stream = stream.unwrap();
         println!("Incoming!!!!");
-        task::spawn(async move { client_loop(stream).await });
+        let server = server.clone();
+        task::spawn(async move { client_loop(stream, server).await });
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum ProtocolState {
-    Handshaking,
-    Status,
-    Login,
-    Play,
+/// Packets read from and written to a client, compressed and encrypted once enabled.
+type Connection = Framed<CipherStream<TcpStream>, McCodec>;
+
+struct Server {
+    config: ServerConfig,
+    players: Mutex<Vec<GameProfile>>,
+    /// Chunks loaded by any player, saved once they change.
+    world: Arc<World>,
+    ticks: AtomicU64,
+    key: ServerKey,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            world: Arc::new(World::new(
+                config.generator.clone(),
+                RegionStorage::new(config.world_directory.join("region")),
+            )),
+            config,
+            players: Mutex::new(Vec::new()),
+            ticks: AtomicU64::new(0),
+            key: ServerKey::generate(),
+        }
+    }
+
+    /// Writes the chunks changed since the last save to their region files.
+    fn save(&self) {
+        match self.world.save() {
+            Ok(0) => (),
+            Ok(saved) => println!("Saved {} chunks.", saved),
+            Err(e) => println!("Could not save chunks: {}", e),
+        }
+    }
+
+    /// Returns the server list status shown to a client of `version`.
+    fn status(&self, version: ProtocolVersion) -> StatusResponse {
+        let players = self
+            .players
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|profile| PlayerSample {
+                name: profile.name.clone(),
+                id: profile.id.to_string(),
+            })
+            .collect();
+        StatusResponse::new(
+            version.name(),
+            version.protocol(),
+            self.config.max_players,
+            players,
+            &self.config.motd,
+        )
+    }
 }
 
 struct Client {
     state: ProtocolState,
+    version: ProtocolVersion,
+    /// Identity of the player, known once the login succeeded.
+    profile: Option<GameProfile>,
+    compression: Compression,
+    encryption: Encryption,
+    /// Name and verify token of a login waiting for Encryption Response.
+    encryption_request: Option<(String, [u8; 4])>,
+    /// Cleared to close the connection after the current packet.
+    connected: bool,
+    /// Chunks sent to the player.
+    sent_chunks: HashSet<(i32, i32)>,
+    /// Column the chunks sent to the player are around.
+    view_center: Option<(i32, i32)>,
 }
 
 impl Client {
     pub fn new() -> Self {
         Self {
             state: ProtocolState::Handshaking,
+            version: ProtocolVersion::LATEST,
+            profile: None,
+            compression: Compression::new(),
+            encryption: Encryption::new(),
+            encryption_request: None,
+            connected: true,
+            sent_chunks: HashSet::new(),
+            view_center: None,
         }
     }
 }
@@ -53,160 +151,258 @@ impl Default for Client {
     }
 }
 
-async fn client_loop(stream: TcpStream) {
-    let mut framed = Framed::new(&stream, McCodec);
-    let mut writer = BufWriter::new(&stream);
+async fn client_loop(stream: TcpStream, server: Arc<Server>) {
     let mut client = Client::new();
-    while let Some(frame) = framed.next().await.transpose().unwrap() {
-        let report = dispatch(frame, &mut writer, &mut client).await;
-        if report.is_err() {
-            println!("{:?}", report);
+    let mut connection = Framed::new(
+        CipherStream::new(stream, client.encryption.clone()),
+        McCodec::with_compression(client.compression.clone())
+            .with_max_packet_length(server.config.max_packet_length),
+    );
+    while let Some(frame) = connection.next().await {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                println!("Closing connection: {}", e);
+                break;
+            }
+        };
+        let report = match frame {
+            Frame::Packet(data) => dispatch(data, &mut connection, &mut client, &server).await,
+            Frame::LegacyPing => {
+                if let Err(report) = legacy_ping(&mut connection, &server).await {
+                    println!("{:?}", report);
+                }
+                break;
+            }
+            Frame::Raw(_) => unreachable!("raw frames are only ever written"),
+        };
+        if let Err(report) = report {
+            println!("Closing connection: {:?}", report);
+            break;
         }
+        if !client.connected {
+            break;
+        }
+    }
+    for location in client.sent_chunks.iter() {
+        server.world.remove_viewer(*location);
+    }
+    if let Some(profile) = client.profile {
+        server.players.lock().unwrap().retain(|p| p.id != profile.id);
     }
-    drop(stream);
 }
 
 async fn dispatch(
     data: Vec<u8>,
-    writer: &mut BufWriter<&TcpStream>,
+    connection: &mut Connection,
     client: &mut Client,
+    server: &Server,
 ) -> Result<(), Report> {
-    let mut reader = McBytesReader::from_vec(data);
-    let packet_id = reader.read_varint()?;
-    match client.state {
-        ProtocolState::Handshaking => match packet_id {
-            0x00 => {
-                handshake(&mut reader).await?;
-                client.state = ProtocolState::Login;
+    let mut reader =
+        McBytesReader::from_vec(data).with_max_string_length(server.config.max_string_length);
+    let result = match client.state {
+        ProtocolState::Handshaking => match ServerboundHandshaking::decode(&mut reader, client.version) {
+            Ok(ServerboundHandshaking::Handshake(packet)) => {
+                handshake(packet, connection, client).await?;
+                Ok(())
             }
-            _ => println!("Got unsupported packet id: {:x}", packet_id),
+            Err(e) => Err(e),
         },
-        ProtocolState::Login => match packet_id {
-            0x00 => {
-                login_start(&mut reader, writer).await?;
-                client.state = ProtocolState::Play;
+        ProtocolState::Status => match ServerboundStatus::decode(&mut reader, client.version) {
+            Ok(ServerboundStatus::Request(_)) => {
+                send(connection, client, &server.status(client.version).packet()).await?;
+                Ok(())
             }
-            _ => println!("Got unsupported packet id: {:x}", packet_id),
+            Ok(ServerboundStatus::Ping(ping)) => {
+                send(connection, client, &Pong { payload: ping.payload }).await?;
+                Ok(())
+            }
+            Err(e) => Err(e),
         },
-        ProtocolState::Play => match packet_id {
-            0x00 => {
-                println!("Teleport confirmed ID: {}", reader.read_varint()?);
+        ProtocolState::Login => match ServerboundLogin::decode(&mut reader, client.version) {
+            Ok(ServerboundLogin::LoginStart(packet)) => {
+                if let Some(profile) = login_start(packet.name, connection, client, server).await? {
+                    finish_login(connection, client, server, profile).await?;
+                }
+                Ok(())
             }
-            0x11 => {
-                let x = reader.read_double()?;
-                let y = reader.read_double()?;
-                let z = reader.read_double()?;
-                let ground = reader.read_one_byte()?;
+            Ok(ServerboundLogin::EncryptionResponse(packet)) => {
+                let profile = encryption_response(packet, connection, client, server).await?;
+                finish_login(connection, client, server, profile).await?;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        },
+        ProtocolState::Play => match ServerboundPlay::decode(&mut reader, client.version) {
+            Ok(ServerboundPlay::TeleportConfirm(packet)) => {
+                println!("Teleport confirmed ID: {}", packet.teleport_id.0);
+                Ok(())
+            }
+            Ok(ServerboundPlay::PlayerPosition(packet)) => {
                 println!("{:.2}, {:.2}, {:.2}, ground: {}",
-                    x, y, z, ground);
+                    packet.x, packet.y, packet.z, packet.on_ground);
+                update_view(connection, client, server, (packet.x, packet.z)).await?;
+                Ok(())
             }
-            0x0f => {
+            Ok(ServerboundPlay::PlayerPositionAndRotation(packet)) => {
+                update_view(connection, client, server, (packet.x, packet.z)).await?;
+                Ok(())
             }
-            0x2a => {
+            Ok(ServerboundPlay::KeepAlive(_)) => Ok(()),
+            Ok(ServerboundPlay::Animation(_)) => {
                 use std::time::Duration;
                 task::sleep(Duration::from_millis(20)).await;
-                if let ProtocolState::Play = client.state {
-                    use std::time::SystemTime;
-                    let ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
-                    let mut builder = PacketBuilder::new();
-                    builder.push_varint(0x21);
-                    builder.push_long(ts as i64 % 11121);
-                    let buf = builder.build();
-                    writer.write(buf.as_slice()).await?;
-                    writer.flush().await?;
-                    // println!("sent keep alive");
-                    ticks(writer).await?;
-                }
+                use std::time::SystemTime;
+                let ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+                let keep_alive = play_cb::KeepAlive {
+                    keep_alive_id: ts as i64 % 11121,
+                };
+                send(connection, client, &keep_alive).await?;
+                // println!("sent keep alive");
+                ticks(connection, client, server).await?;
+                Ok(())
             }
-            id => {
-                println!("got {:#2x} on play", id);
+            Ok(ServerboundPlay::ChatMessage(packet)) => {
+                let name = client.profile.as_ref().map_or("?", |p| p.name.as_str());
+                println!("<{}> {}", name, packet.message);
+                Ok(())
             }
-            // _ => println!("Got unsupported packet id: {:x}", packet_id),
+            Ok(packet) => {
+                println!("{:?}", packet);
+                Ok(())
+            }
+            Err(e) => Err(e),
         },
-        _ => println!("Unsupported state {:?}", client.state),
+    };
+    match result {
+        Err(McBytesErr::UnknownPacketId(id)) => {
+            println!("Got unsupported packet id {:#x} on {:?}", id, client.state);
+            Ok(())
+        }
+        result => Ok(result?),
     }
+}
+
+/// Writes a packet in the client's protocol version.
+async fn send<P: Clientbound>(
+    connection: &mut Connection,
+    client: &Client,
+    packet: &P,
+) -> Result<(), Report> {
+    let data = packet.to_builder(client.version).into_inner();
+    connection.send(Frame::Packet(data)).await?;
+    Ok(())
+}
+
+async fn legacy_ping(connection: &mut Connection, server: &Server) -> Result<(), Report> {
+    let buf = server.status(ProtocolVersion::LATEST).build_legacy();
+    connection.send(Frame::Raw(buf)).await?;
     Ok(())
 }
 
-async fn login_start(reader: &mut McBytesReader, writer: &mut BufWriter<&TcpStream>) -> Result<(), Report> {
+async fn login_start(
+    name: String,
+    connection: &mut Connection,
+    client: &mut Client,
+    server: &Server,
+) -> Result<Option<GameProfile>, Report> {
     // see https://wiki.vg/Protocol_FAQ#What.27s_the_normal_login_sequence_for_a_client.3F
-    let name = reader.read_string()?;
     println!("A user {} has requested login!", name);
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x02);
-    builder.push_string("94ec47eb-5961-498b-be0d-25e1f9e4616b");
-    builder.push_string("zynaxsoft");
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
-
-    // Join Game
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x26);
-    builder.push_int(100);
-    builder.push_byte(0);
-    builder.push_int(0);
-    builder.push_long(1);
-    builder.push_byte(1);
-    builder.push_string("default");
-    builder.push_varint(10);
-    builder.push_bool(true);
-    builder.push_bool(false);
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
-
-    // Inventory
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x15);
-    builder.push_byte(1);
-    builder.push_short(0);
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
-
-    // Spawn Position
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x4e);
-    builder.push_position(0, 0, 0);
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
-
-    // Chunk Data
-    let chunk_column = ChunkColumn::new((0, 0));
-    let chunk_packet = ChunkPacket::new(chunk_column);
-    let buf = chunk_packet.build();
-    writer.write(buf.as_slice()).await?;
-    let chunk_column = ChunkColumn::new((1, 0));
-    let chunk_packet = ChunkPacket::new(chunk_column);
-    let buf = chunk_packet.build();
-    writer.write(buf.as_slice()).await?;
-    let chunk_column = ChunkColumn::new((0, 1));
-    let chunk_packet = ChunkPacket::new(chunk_column);
-    let buf = chunk_packet.build();
-    writer.write(buf.as_slice()).await?;
-    let chunk_column = ChunkColumn::new((1, 1));
-    let chunk_packet = ChunkPacket::new(chunk_column);
-    let buf = chunk_packet.build();
-    writer.write(buf.as_slice()).await?;
-    println!("sent chunk.");
+    if !server.config.online_mode {
+        return Ok(Some(GameProfile::offline(&name)));
+    }
+
+    let token = auth::verify_token();
+    let request = login_cb::EncryptionRequest {
+        server_id: String::new(),
+        public_key: server.key.public_der().to_vec(),
+        verify_token: token.to_vec(),
+    };
+    send(connection, client, &request).await?;
+    client.encryption_request = Some((name, token));
+    Ok(None)
+}
 
-    // Lighting
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x25);
-    builder.push_varint(1);
-    builder.push_varint(1);
-    builder.push_varint(0b11_1111_1111_1111_1111);
-    builder.push_varint(0b11_1111_1111_1111_1111);
-    builder.push_varint(0b11_1111_1111_1111_1111);
-    builder.push_varint(0b11_1111_1111_1111_1111);
-    for _ in 0..18 {
-        builder.push_varint(2048);
-        builder.push_vec_u8(&[0xFF; 2048]);
+async fn encryption_response(
+    packet: EncryptionResponse,
+    connection: &mut Connection,
+    client: &mut Client,
+    server: &Server,
+) -> Result<GameProfile, Report> {
+    let (name, token) = client
+        .encryption_request
+        .take()
+        .ok_or_else(|| eyre!("Got Encryption Response without a request"))?;
+    let secret = server
+        .key
+        .decrypt_response(&packet.shared_secret, &packet.verify_token, &token)?;
+    client.encryption.enable(secret);
+
+    let hash = auth::server_hash("", &secret, server.key.public_der());
+    let sessions = server.config.sessions.clone();
+    let username = name.clone();
+    // The session server is queried with a blocking client, keep it off the executor.
+    let profile = task::spawn_blocking(move || sessions.has_joined(&username, &hash)).await?;
+    match profile {
+        Some(profile) => Ok(profile),
+        None => {
+            let disconnect = login_cb::Disconnect {
+                reason: serde_json::json!({ "text": "Failed to verify username!" }).to_string(),
+            };
+            send(connection, client, &disconnect).await?;
+            client.connected = false;
+            Err(eyre!("{} failed session verification", name))
+        }
     }
-    for _ in 0..18 {
-        builder.push_varint(2048);
-        builder.push_vec_u8(&[0xFF; 2048]);
+}
+
+async fn finish_login(
+    connection: &mut Connection,
+    client: &mut Client,
+    server: &Server,
+    profile: GameProfile,
+) -> Result<(), Report> {
+    if let Some(threshold) = server.config.compression_threshold {
+        let set_compression = login_cb::SetCompression {
+            threshold: VarInt(threshold as i32),
+        };
+        send(connection, client, &set_compression).await?;
+        client.compression.enable(threshold);
     }
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
+
+    let login_success = login_cb::LoginSuccess {
+        uuid: profile.id.to_string(),
+        username: profile.name.clone(),
+    };
+    send(connection, client, &login_success).await?;
+
+    let join_game = play_cb::JoinGame {
+        entity_id: 100,
+        gamemode: 0,
+        dimension: 0,
+        hashed_seed: 1,
+        max_players: 1,
+        level_type: server.world.generator().level_type().to_owned(),
+        view_distance: VarInt(server.config.view_distance as i32),
+        reduced_debug_info: true,
+        enable_respawn_screen: false,
+    };
+    send(connection, client, &join_game).await?;
+
+    let inventory = play_cb::WindowItems {
+        window_id: 1,
+        count: 0,
+        slots: RawBytes(Vec::new()),
+    };
+    send(connection, client, &inventory).await?;
+
+    let spawn_position = play_cb::SpawnPosition {
+        location: Position { x: 0, y: 0, z: 0 },
+    };
+    send(connection, client, &spawn_position).await?;
+
+    update_view(connection, client, server, (0.0, 0.0)).await?;
+    println!("sent chunk.");
 
     use std::time::Duration;
     task::sleep(Duration::from_millis(200)).await;
@@ -214,50 +410,133 @@ async fn login_start(reader: &mut McBytesReader, writer: &mut BufWriter<&TcpStre
     use std::time::SystemTime;
     let ts = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
 
-    // Player Position And Look
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x36);
-    builder.push_double(0.0);
-    builder.push_double(64.0);
-    builder.push_double(0.0);
-    builder.push_float(0.0);
-    builder.push_float(0.0);
-    // builder.push_byte(0b0000_10101);
-    builder.push_byte(0);
-    builder.push_varint(ts as i32 % 237845);
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
+    let position_and_look = play_cb::PlayerPositionAndLook {
+        x: 0.0,
+        y: 64.0,
+        z: 0.0,
+        yaw: 0.0,
+        pitch: 0.0,
+        flags: 0,
+        teleport_id: VarInt(ts as i32 % 237845),
+    };
+    send(connection, client, &position_and_look).await?;
     println!("sent player position!");
 
-    writer.flush().await?;
 
+    println!("{} logged in as {}", profile.name, profile.id);
+    server.players.lock().unwrap().push(profile.clone());
+    client.profile = Some(profile);
+    client.state = ProtocolState::Play;
     Ok(())
 }
 
-static mut server_ticks: u64 = 0;
+/// Moves the view of the player to the column of a position once they enter
+/// it, unloading the chunks which are now out of view and sending the new
+/// ones nearest first.
+async fn update_view(
+    connection: &mut Connection,
+    client: &mut Client,
+    server: &Server,
+    (x, z): (f64, f64),
+) -> Result<(), Report> {
+    let center = ((x.floor() as i32) >> 4, (z.floor() as i32) >> 4);
+    if client.view_center == Some(center) {
+        return Ok(());
+    }
+    let radius = server.config.view_distance as i32;
+    let change = view_change(client.view_center, center, radius, &client.sent_chunks);
+    client.view_center = Some(center);
+    let update_view_position = play_cb::UpdateViewPosition {
+        chunk_x: VarInt(center.0),
+        chunk_z: VarInt(center.1),
+    };
+    send(connection, client, &update_view_position).await?;
 
-async fn ticks(writer: &mut BufWriter<&TcpStream>) -> Result<(), Report> {
-    unsafe {
-        server_ticks += 1;
+    for location in change.unload.into_iter() {
+        client.sent_chunks.remove(&location);
+        server.world.remove_viewer(location);
+        let unload_chunk = play_cb::UnloadChunk {
+            chunk_x: location.0,
+            chunk_z: location.1,
+        };
+        send(connection, client, &unload_chunk).await?;
+    }
+
+    for location in change.load.into_iter() {
+        // loading, generating and encoding chunks blocks on files and takes a while
+        let (world, version) = (server.world.clone(), client.version);
+        let (loaded, chunk) = task::spawn_blocking(move || {
+            let loaded = world.add_viewer(location);
+            (loaded, world.encoded_chunk(location, version))
+        })
+        .await;
+        if let Err(e) = loaded {
+            println!("Could not load chunk {:?}, generated it: {}", location, e);
+        }
+        client.sent_chunks.insert(location);
+        let chunk = chunk.ok_or_else(|| eyre!("Chunk {:?} is not loaded", location))?;
+        connection.send(Frame::Packet(chunk.update_light.clone())).await?;
+        connection.send(Frame::Packet(chunk.chunk_data.clone())).await?;
     }
-    let mut builder = PacketBuilder::new();
-    builder.push_varint(0x4f);
-    unsafe {builder.push_long(server_ticks as i64);}
-    builder.push_long(6000);
-    let buf = builder.build();
-    writer.write(buf.as_slice()).await?;
-    writer.flush().await?;
     Ok(())
 }
 
-async fn handshake(reader: &mut McBytesReader) -> Result<(), Report> {
-    let protocol_version = reader.read_varint()?;
-    let server_address = reader.read_string()?;
-    let server_port = reader.read_unsigned_short()?;
-    let next_state = reader.read_varint()?;
+/// Saves the chunks which changed every `autosave_interval`.
+async fn autosave_loop(server: Arc<Server>) {
+    loop {
+        task::sleep(server.config.autosave_interval).await;
+        let server = server.clone();
+        task::spawn_blocking(move || server.save()).await;
+    }
+}
+
+/// Saves and unloads the chunks no player looks at every `unload_interval`.
+async fn unload_loop(server: Arc<Server>) {
+    loop {
+        task::sleep(server.config.unload_interval).await;
+        let world = server.world.clone();
+        match task::spawn_blocking(move || world.unload_unused()).await {
+            Ok(0) => (),
+            Ok(unloaded) => println!("Unloaded {} chunks.", unloaded),
+            Err(e) => println!("Could not save unused chunks: {}", e),
+        }
+    }
+}
+
+async fn ticks(connection: &mut Connection, client: &Client, server: &Server) -> Result<(), Report> {
+    let server_ticks = server.ticks.fetch_add(1, Ordering::Relaxed) + 1;
+    let time_update = play_cb::TimeUpdate {
+        world_age: server_ticks as i64,
+        time_of_day: 6000,
+    };
+    send(connection, client, &time_update).await?;
+    Ok(())
+}
+
+async fn handshake(packet: Handshake, connection: &mut Connection, client: &mut Client) -> Result<(), Report> {
     println!(
         "{} {} {} {}",
-        protocol_version, server_address, server_port, next_state
+        packet.protocol_version.0, packet.server_address, packet.server_port, packet.next_state.0
     );
+    let version = ProtocolVersion::from_protocol(packet.protocol_version.0);
+    client.version = version.unwrap_or(ProtocolVersion::LATEST);
+    client.state = match packet.next_state.0 {
+        1 => ProtocolState::Status,
+        2 => ProtocolState::Login,
+        _ => return Err(eyre!("Unknown next state: {}", packet.next_state.0)),
+    };
+    if let (ProtocolState::Login, None) = (client.state, version) {
+        let message = format!(
+            "Unsupported protocol version {}! Please use one of {}.",
+            packet.protocol_version.0,
+            ProtocolVersion::supported_names(),
+        );
+        let disconnect = login_cb::Disconnect {
+            reason: serde_json::json!({ "text": message }).to_string(),
+        };
+        send(connection, client, &disconnect).await?;
+        client.connected = false;
+        return Err(eyre!("Client with protocol {} disconnected", packet.protocol_version.0));
+    }
     Ok(())
 }
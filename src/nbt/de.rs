@@ -0,0 +1,346 @@
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+use std::fmt::{self, Display};
+
+use crate::nbt::{
+    Compound, NbtErr, Tag, BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN, TAG_TOKEN,
+};
+
+/// Converts a tag to a value, the reverse of `to_tag`.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, NbtErr> {
+    T::deserialize(tag)
+}
+
+impl de::Error for NbtErr {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtErr::Message(msg.to_string())
+    }
+}
+
+impl<'de> IntoDeserializer<'de, NbtErr> for Tag {
+    type Deserializer = Tag;
+
+    fn into_deserializer(self) -> Tag {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = NbtErr;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Byte(v) => visitor.visit_i8(v),
+            Tag::Short(v) => visitor.visit_i16(v),
+            Tag::Int(v) => visitor.visit_i32(v),
+            Tag::Long(v) => visitor.visit_i64(v),
+            Tag::Float(v) => visitor.visit_f32(v),
+            Tag::Double(v) => visitor.visit_f64(v),
+            Tag::ByteArray(v) => visitor.visit_seq(ListAccess(v.into_iter().map(Tag::Byte))),
+            Tag::String(v) => visitor.visit_string(v),
+            Tag::List(v) => visitor.visit_seq(ListAccess(v.into_iter())),
+            Tag::Compound(v) => visitor.visit_map(CompoundAccess {
+                iter: v.into_iter(),
+                value: None,
+            }),
+            Tag::IntArray(v) => visitor.visit_seq(ListAccess(v.into_iter().map(Tag::Int))),
+            Tag::LongArray(v) => visitor.visit_seq(ListAccess(v.into_iter().map(Tag::Long))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Byte(v) => visitor.visit_bool(v != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    // NBT has no unsigned types, the bits are kept as they are.
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Byte(v) => visitor.visit_u8(v as u8),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Short(v) => visitor.visit_u16(v as u16),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Int(v) => visitor.visit_u32(v as u32),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::Long(v) => visitor.visit_u64(v as u64),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::ByteArray(v) => visitor.visit_byte_buf(v.into_iter().map(|b| b as u8).collect()),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Present tags are always `Some`, missing fields become `None` on their own.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        visitor.visit_some(self)
+    }
+
+    /// Arrays deserialized as tags are maps of a single entry named after
+    /// their type, other values would only see them as lists.
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, NbtErr> {
+        let (token, items) = match (name, self) {
+            (TAG_TOKEN, Tag::ByteArray(v)) => (BYTE_ARRAY_TOKEN, v.into_iter().map(Tag::Byte).collect()),
+            (TAG_TOKEN, Tag::IntArray(v)) => (INT_ARRAY_TOKEN, v.into_iter().map(Tag::Int).collect()),
+            (TAG_TOKEN, Tag::LongArray(v)) => (LONG_ARRAY_TOKEN, v.into_iter().map(Tag::Long).collect()),
+            (TAG_TOKEN, tag) => return tag.deserialize_any(visitor),
+            (_, tag) => return visitor.visit_newtype_struct(tag),
+        };
+        let mut array = Compound::new();
+        array.insert(String::from(token), Tag::List(items));
+        visitor.visit_map(CompoundAccess {
+            iter: array.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, NbtErr> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(VariantTag { variant, value })
+            }
+            _ => Err(NbtErr::Message(String::from(
+                "Enums must be a string or a compound with a single tag.",
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtErr> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(TAG_TOKEN, TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an NBT tag")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Tag, E> {
+        Ok(Tag::from(v))
+    }
+
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn visit_i16<E: de::Error>(self, v: i16) -> Result<Tag, E> {
+        Ok(Tag::Short(v))
+    }
+
+    fn visit_i32<E: de::Error>(self, v: i32) -> Result<Tag, E> {
+        Ok(Tag::Int(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tag, E> {
+        Ok(Tag::Long(v))
+    }
+
+    // NBT has no unsigned types, the bits are kept as they are.
+    fn visit_u8<E: de::Error>(self, v: u8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn visit_u16<E: de::Error>(self, v: u16) -> Result<Tag, E> {
+        Ok(Tag::Short(v as i16))
+    }
+
+    fn visit_u32<E: de::Error>(self, v: u32) -> Result<Tag, E> {
+        Ok(Tag::Int(v as i32))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tag, E> {
+        Ok(Tag::Long(v as i64))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<Tag, E> {
+        Ok(Tag::Float(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Tag, E> {
+        Ok(Tag::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+        Ok(Tag::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Tag, E> {
+        Ok(Tag::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Tag, E> {
+        Ok(Tag::ByteArray(v.iter().map(|b| *b as i8).collect()))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        Tag::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+        let mut items: Vec<Tag> = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(tag) = seq.next_element::<Tag>()? {
+            if items.first().is_some_and(|first| first.tag_type() != tag.tag_type()) {
+                return Err(de::Error::custom(NbtErr::MixedList));
+            }
+            items.push(tag);
+        }
+        Ok(Tag::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+        let mut compound = Compound::new();
+        while let Some(name) = map.next_key::<String>()? {
+            let tag = match name.as_str() {
+                BYTE_ARRAY_TOKEN => return Ok(Tag::ByteArray(map.next_value()?)),
+                INT_ARRAY_TOKEN => return Ok(Tag::IntArray(map.next_value()?)),
+                LONG_ARRAY_TOKEN => return Ok(Tag::LongArray(map.next_value()?)),
+                _ => map.next_value()?,
+            };
+            compound.insert(name, tag);
+        }
+        Ok(Tag::Compound(compound))
+    }
+}
+
+struct ListAccess<I>(I);
+
+impl<'de, I: Iterator<Item = Tag>> SeqAccess<'de> for ListAccess<I> {
+    type Error = NbtErr;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, NbtErr> {
+        self.0.next().map(|tag| seed.deserialize(tag)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint().1
+    }
+}
+
+struct CompoundAccess {
+    iter: indexmap::map::IntoIter<String, Tag>,
+    value: Option<Tag>,
+}
+
+impl<'de> MapAccess<'de> for CompoundAccess {
+    type Error = NbtErr;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, NbtErr> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, NbtErr> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| NbtErr::Message(String::from("Value requested before its key.")))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// A variant stored as a compound holding one tag named after it.
+struct VariantTag {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> EnumAccess<'de> for VariantTag {
+    type Error = NbtErr;
+    type Variant = Tag;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Tag), NbtErr> {
+        let variant = seed.deserialize(IntoDeserializer::<NbtErr>::into_deserializer(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Tag {
+    type Error = NbtErr;
+
+    fn unit_variant(self) -> Result<(), NbtErr> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, NbtErr> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, NbtErr> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, NbtErr> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
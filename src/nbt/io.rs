@@ -0,0 +1,200 @@
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use crate::nbt::{Compound, Compression, NbtErr, Tag, TagType};
+use crate::packet::builder::PacketBuilder;
+use crate::packet::reader::{McBytesErr, McBytesReader};
+use crate::packet::types::McEncode;
+
+/// Deepest nesting of lists and compounds accepted, as in vanilla.
+pub(super) const MAX_DEPTH: usize = 512;
+
+/// Largest decompressed file accepted, far over what any chunk or level
+/// takes but small enough that a few bytes of zlib cannot use up the memory.
+pub(super) const MAX_DECOMPRESSED_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// Reads a named tag, `None` for a lone TAG_End.
+pub fn read_tag(reader: &mut McBytesReader) -> Result<Option<(String, Tag)>, McBytesErr> {
+    let tag_type = read_type(reader)?;
+    if tag_type == TagType::End {
+        return Ok(None);
+    }
+    let name = read_string(reader)?;
+    let tag = read_payload(reader, tag_type, 0)?;
+    Ok(Some((name, tag)))
+}
+
+pub fn write_tag(builder: &mut PacketBuilder, name: &str, tag: &Tag) -> Result<(), NbtErr> {
+    builder.push_byte(tag.tag_type() as u8);
+    write_string(builder, name)?;
+    write_payload(builder, tag)
+}
+
+/// Reads a named tag from a file, detecting whether it is gzip, zlib or not compressed.
+pub fn read_compressed(data: &[u8]) -> Result<(String, Tag), NbtErr> {
+    let mut decompressed = Vec::new();
+    // one byte over the maximum tells files which are too long from those
+    // which are just as long
+    let limit = MAX_DECOMPRESSED_LENGTH + 1;
+    let data = match data {
+        [0x1f, 0x8b, ..] => {
+            GzDecoder::new(data).take(limit).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        [0x78, ..] => {
+            ZlibDecoder::new(data).take(limit).read_to_end(&mut decompressed)?;
+            decompressed
+        }
+        _ => data.to_vec(),
+    };
+    if data.len() as u64 > MAX_DECOMPRESSED_LENGTH {
+        return Err(NbtErr::TooLong);
+    }
+    read_tag(&mut McBytesReader::from_vec(data))?.ok_or(NbtErr::MissingRoot)
+}
+
+pub fn write_compressed(name: &str, tag: &Tag, compression: Compression) -> Result<Vec<u8>, NbtErr> {
+    let mut builder = PacketBuilder::new();
+    write_tag(&mut builder, name, tag)?;
+    let data = builder.into_inner();
+    let data = match compression {
+        Compression::None => data,
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            encoder.finish()?
+        }
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            encoder.finish()?
+        }
+    };
+    Ok(data)
+}
+
+fn read_type(reader: &mut McBytesReader) -> Result<TagType, McBytesErr> {
+    let id = reader.read_unsigned_byte()?;
+    TagType::try_from(id).map_err(|_| McBytesErr::InvalidNbtTag(id))
+}
+
+fn read_string(reader: &mut McBytesReader) -> Result<String, McBytesErr> {
+    let length = reader.read_unsigned_short()? as usize;
+    let bytes = reader.read_bytes(length)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Reads the i32 length of an array or list, which may not be negative or
+/// longer than what is left to read.
+fn read_length(reader: &mut McBytesReader) -> Result<usize, McBytesErr> {
+    let length = reader.read_int()?;
+    if length < 0 {
+        return Err(McBytesErr::NegativeLength(length));
+    }
+    if length as usize > reader.remaining() {
+        return Err(McBytesErr::InsufficientBytes);
+    }
+    Ok(length as usize)
+}
+
+fn read_array<T>(
+    reader: &mut McBytesReader,
+    read: impl Fn(&mut McBytesReader) -> Result<T, McBytesErr>,
+) -> Result<Vec<T>, McBytesErr> {
+    let length = read_length(reader)?;
+    let mut result = Vec::with_capacity(length);
+    for _ in 0..length {
+        result.push(read(reader)?);
+    }
+    Ok(result)
+}
+
+fn read_payload(reader: &mut McBytesReader, tag_type: TagType, depth: usize) -> Result<Tag, McBytesErr> {
+    if depth > MAX_DEPTH {
+        return Err(McBytesErr::NbtTooDeep);
+    }
+    let tag = match tag_type {
+        TagType::End => return Err(McBytesErr::InvalidNbtTag(0)),
+        TagType::Byte => Tag::Byte(reader.read_byte()?),
+        TagType::Short => Tag::Short(reader.read_short()?),
+        TagType::Int => Tag::Int(reader.read_int()?),
+        TagType::Long => Tag::Long(reader.read_long()?),
+        TagType::Float => Tag::Float(reader.read_float()?),
+        TagType::Double => Tag::Double(reader.read_double()?),
+        TagType::ByteArray => Tag::ByteArray(read_array(reader, McBytesReader::read_byte)?),
+        TagType::String => Tag::String(read_string(reader)?),
+        TagType::List => {
+            let item_type = read_type(reader)?;
+            let length = read_length(reader)?;
+            let mut list = Vec::with_capacity(length);
+            for _ in 0..length {
+                list.push(read_payload(reader, item_type, depth + 1)?);
+            }
+            Tag::List(list)
+        }
+        TagType::Compound => {
+            let mut compound = Compound::new();
+            loop {
+                let item_type = read_type(reader)?;
+                if item_type == TagType::End {
+                    break;
+                }
+                let name = read_string(reader)?;
+                compound.insert(name, read_payload(reader, item_type, depth + 1)?);
+            }
+            Tag::Compound(compound)
+        }
+        TagType::IntArray => Tag::IntArray(read_array(reader, McBytesReader::read_int)?),
+        TagType::LongArray => Tag::LongArray(read_array(reader, McBytesReader::read_long)?),
+    };
+    Ok(tag)
+}
+
+fn write_string(builder: &mut PacketBuilder, string: &str) -> Result<(), NbtErr> {
+    let length = u16::try_from(string.len()).map_err(|_| NbtErr::StringTooLong(string.len()))?;
+    builder.push_unsigned_char(length);
+    builder.push_vec_u8(string.as_bytes());
+    Ok(())
+}
+
+fn write_array<T: McEncode>(builder: &mut PacketBuilder, array: &[T]) {
+    builder.push_int(array.len() as i32);
+    for value in array.iter() {
+        value.encode(builder);
+    }
+}
+
+fn write_payload(builder: &mut PacketBuilder, tag: &Tag) -> Result<(), NbtErr> {
+    match tag {
+        Tag::Byte(value) => value.encode(builder),
+        Tag::Short(value) => value.encode(builder),
+        Tag::Int(value) => value.encode(builder),
+        Tag::Long(value) => value.encode(builder),
+        Tag::Float(value) => value.encode(builder),
+        Tag::Double(value) => value.encode(builder),
+        Tag::ByteArray(array) => write_array(builder, array),
+        Tag::String(string) => write_string(builder, string)?,
+        Tag::List(list) => {
+            let item_type = list.first().map_or(TagType::End, Tag::tag_type);
+            builder.push_byte(item_type as u8);
+            builder.push_int(list.len() as i32);
+            for item in list.iter() {
+                write_payload(builder, item)?;
+            }
+        }
+        Tag::Compound(compound) => {
+            for (name, item) in compound.iter() {
+                write_tag(builder, name, item)?;
+            }
+            builder.push_byte(TagType::End as u8);
+        }
+        Tag::IntArray(array) => write_array(builder, array),
+        Tag::LongArray(array) => write_array(builder, array),
+    }
+    Ok(())
+}
@@ -0,0 +1,344 @@
+//! Named Binary Tag, the format of item data, block entities and world files.
+// see https://wiki.vg/NBT
+
+mod de;
+mod io;
+mod ser;
+pub mod snbt;
+
+pub use de::from_tag;
+pub use io::{read_compressed, read_tag, write_compressed, write_tag};
+pub use ser::to_tag;
+
+use indexmap::IndexMap;
+use num_enum::TryFromPrimitive;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::packet::builder::PacketBuilder;
+use crate::packet::reader::{McBytesErr, McBytesReader};
+
+#[derive(Debug, Error)]
+pub enum NbtErr {
+    #[error(transparent)]
+    Bytes(#[from] McBytesErr),
+    #[error("Could not (de)compress NBT: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("List elements must all have the same type.")]
+    MixedList,
+    #[error("Expected a named tag, found TAG_End.")]
+    MissingRoot,
+    #[error("String of {0} bytes is too long for NBT.")]
+    StringTooLong(usize),
+    #[error("NBT is too long once decompressed.")]
+    TooLong,
+    #[error(transparent)]
+    Snbt(#[from] snbt::SnbtErr),
+    #[error("{0}")]
+    Message(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u8)]
+pub enum TagType {
+    End = 0,
+    Byte = 1,
+    Short = 2,
+    Int = 3,
+    Long = 4,
+    Float = 5,
+    Double = 6,
+    ByteArray = 7,
+    String = 8,
+    List = 9,
+    Compound = 10,
+    IntArray = 11,
+    LongArray = 12,
+}
+
+/// Named tags of a compound, in the order they were read or inserted.
+pub type Compound = IndexMap<String, Tag>;
+
+/// Payload of any tag but TAG_End, which only ever terminates a compound.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// Elements all have the same type.
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn tag_type(&self) -> TagType {
+        match self {
+            Tag::Byte(_) => TagType::Byte,
+            Tag::Short(_) => TagType::Short,
+            Tag::Int(_) => TagType::Int,
+            Tag::Long(_) => TagType::Long,
+            Tag::Float(_) => TagType::Float,
+            Tag::Double(_) => TagType::Double,
+            Tag::ByteArray(_) => TagType::ByteArray,
+            Tag::String(_) => TagType::String,
+            Tag::List(_) => TagType::List,
+            Tag::Compound(_) => TagType::Compound,
+            Tag::IntArray(_) => TagType::IntArray,
+            Tag::LongArray(_) => TagType::LongArray,
+        }
+    }
+
+    /// Returns the tag named `name` if this is a compound holding one.
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(compound) => compound.get(name),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$ty> for Tag {
+                fn from(value: $ty) -> Self {
+                    Tag::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from! {
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+    Vec<Tag> => List,
+    Compound => Compound,
+}
+
+impl From<&str> for Tag {
+    fn from(value: &str) -> Self {
+        Tag::String(String::from(value))
+    }
+}
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::Byte(value as i8)
+    }
+}
+
+/// How an NBT file is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+/// Name newtype structs serialize with to become arrays rather than lists.
+const BYTE_ARRAY_TOKEN: &str = "__nbt_byte_array";
+const INT_ARRAY_TOKEN: &str = "__nbt_int_array";
+const LONG_ARRAY_TOKEN: &str = "__nbt_long_array";
+/// Name tags deserialize with, for the NBT deserializer to keep arrays apart
+/// from lists.
+const TAG_TOKEN: &str = "__nbt_tag";
+
+macro_rules! array_type {
+    ($(#[$meta:meta])* $name:ident($ty:ty) => $token:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Default)]
+        pub struct $name(pub Vec<$ty>);
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($token, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Vec::deserialize(deserializer).map($name)
+            }
+        }
+    };
+}
+
+array_type! {
+    /// Serialized as a TAG_Byte_Array instead of a list of bytes.
+    ByteArray(i8) => BYTE_ARRAY_TOKEN
+}
+
+array_type! {
+    /// Serialized as a TAG_Int_Array instead of a list of ints.
+    IntArray(i32) => INT_ARRAY_TOKEN
+}
+
+array_type! {
+    /// Serialized as a TAG_Long_Array instead of a list of longs.
+    LongArray(i64) => LONG_ARRAY_TOKEN
+}
+
+/// Reads a value from a named tag, compressed or not.
+pub fn from_bytes<T: DeserializeOwned>(data: &[u8]) -> Result<T, NbtErr> {
+    let (_, tag) = read_compressed(data)?;
+    from_tag(tag)
+}
+
+/// Writes a value as an uncompressed tag named `name`.
+pub fn to_bytes<T: Serialize + ?Sized>(name: &str, value: &T) -> Result<Vec<u8>, NbtErr> {
+    let mut builder = PacketBuilder::new();
+    write_tag(&mut builder, name, &to_tag(value)?)?;
+    Ok(builder.into_inner())
+}
+
+/// Reads a value written in SNBT.
+pub fn from_snbt<T: DeserializeOwned>(input: &str) -> Result<T, NbtErr> {
+    from_tag(snbt::parse(input)?)
+}
+
+/// Reads a value from a named tag sent in a packet.
+pub fn from_reader<T: DeserializeOwned>(reader: &mut McBytesReader) -> Result<T, NbtErr> {
+    let (_, tag) = read_tag(reader)?.ok_or(NbtErr::MissingRoot)?;
+    from_tag(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// hello_world.nbt from the original specification
+    const HELLO_WORLD: &[u8] = &[
+        0x0a, 0x00, 0x0b, b'h', b'e', b'l', b'l', b'o', b' ', b'w', b'o', b'r', b'l', b'd',
+        0x08, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x00, 0x09, b'B', b'a', b'n', b'a', b'n',
+        b'r', b'a', b'm', b'a', 0x00,
+    ];
+
+    fn every_tag() -> Tag {
+        let mut compound = Compound::new();
+        compound.insert(String::from("byte"), Tag::Byte(-1));
+        compound.insert(String::from("short"), Tag::Short(-300));
+        compound.insert(String::from("int"), Tag::Int(1 << 20));
+        compound.insert(String::from("long"), Tag::Long(i64::MIN));
+        compound.insert(String::from("float"), Tag::Float(0.5));
+        compound.insert(String::from("double"), Tag::Double(-0.25));
+        compound.insert(String::from("bytes"), Tag::ByteArray(vec![1, -2, 3]));
+        compound.insert(String::from("string"), Tag::from("minecraft:stone"));
+        compound.insert(String::from("list"), Tag::List(vec![Tag::Short(1), Tag::Short(2)]));
+        compound.insert(String::from("empty"), Tag::List(Vec::new()));
+        compound.insert(String::from("ints"), Tag::IntArray(vec![i32::MAX, 0]));
+        compound.insert(String::from("longs"), Tag::LongArray(vec![-1; 3]));
+        let mut root = Compound::new();
+        root.insert(String::from("nested"), Tag::Compound(compound));
+        Tag::Compound(root)
+    }
+
+    #[test]
+    fn reads_hello_world() {
+        let mut reader = McBytesReader::from_vec(HELLO_WORLD.to_vec());
+        let (name, tag) = read_tag(&mut reader).unwrap().unwrap();
+        assert_eq!(name, "hello world");
+        assert_eq!(tag.get("name"), Some(&Tag::from("Bananrama")));
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn round_trip_every_tag() {
+        for compression in [Compression::None, Compression::Gzip, Compression::Zlib].iter() {
+            let data = write_compressed("root", &every_tag(), *compression).unwrap();
+            let (name, tag) = read_compressed(&data).unwrap();
+            assert_eq!(name, "root");
+            assert_eq!(tag, every_tag());
+        }
+    }
+
+    #[test]
+    fn tags_are_serde_values() {
+        assert_eq!(to_tag(&every_tag()).unwrap(), every_tag());
+        assert_eq!(from_tag::<Tag>(every_tag()).unwrap(), every_tag());
+        let compound: Compound = from_snbt("{a:[B;1b],b:[I;],c:{d:[L;2L]}}").unwrap();
+        assert_eq!(compound["a"], Tag::ByteArray(vec![1]));
+        assert_eq!(compound["b"], Tag::IntArray(Vec::new()));
+        assert_eq!(compound["c"].get("d"), Some(&Tag::LongArray(vec![2])));
+    }
+
+    #[test]
+    fn oversized_tags_are_errors() {
+        let long = Tag::String("a".repeat(u16::MAX as usize + 1));
+        assert!(matches!(to_bytes("", &long), Err(NbtErr::StringTooLong(65536))));
+        assert!(to_bytes(&"a".repeat(u16::MAX as usize), &Tag::Byte(1)).is_ok());
+
+        // a byte array of zeros which compresses to almost nothing
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&[7, 0, 0]).unwrap();
+        encoder.write_all(&(io::MAX_DECOMPRESSED_LENGTH as i32).to_be_bytes()).unwrap();
+        let zeros = vec![0; 1 << 20];
+        for _ in 0..io::MAX_DECOMPRESSED_LENGTH >> 20 {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let data = encoder.finish().unwrap();
+        assert!(matches!(read_compressed(&data), Err(NbtErr::TooLong)));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Facing {
+        North,
+        South,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Item {
+        id: String,
+        count: i8,
+        enchanted: bool,
+        damage: Option<i32>,
+        lore: Vec<String>,
+        facing: Facing,
+        #[serde(rename = "MOTION_BLOCKING")]
+        heights: LongArray,
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let item = Item {
+            id: String::from("minecraft:stone"),
+            count: 64,
+            enchanted: true,
+            damage: None,
+            lore: vec![String::from("a"), String::from("b")],
+            facing: Facing::South,
+            heights: LongArray(vec![1, 2, 3]),
+        };
+        let tag = to_tag(&item).unwrap();
+        assert_eq!(tag.get("Count"), Some(&Tag::Byte(64)));
+        assert_eq!(tag.get("Enchanted"), Some(&Tag::Byte(1)));
+        assert_eq!(tag.get("Damage"), None);
+        assert_eq!(tag.get("Facing"), Some(&Tag::from("South")));
+        assert_eq!(tag.get("MOTION_BLOCKING"), Some(&Tag::LongArray(vec![1, 2, 3])));
+        let data = to_bytes("", &item).unwrap();
+        assert_eq!(from_bytes::<Item>(&data).unwrap(), item);
+        let snbt = r#"{Id:"minecraft:stone",Count:64b,Enchanted:true,Lore:[a,b],Facing:South,MOTION_BLOCKING:[L;1L,2L,3L]}"#;
+        assert_eq!(from_snbt::<Item>(snbt).unwrap(), item);
+    }
+
+    #[test]
+    fn mixed_lists_are_errors() {
+        #[derive(Serialize)]
+        struct Mixed((i32, String));
+        assert!(matches!(to_tag(&Mixed((1, String::from("a")))), Err(NbtErr::MixedList)));
+    }
+}
@@ -0,0 +1,385 @@
+use serde::ser::{self, Serialize};
+
+use std::fmt::Display;
+
+use crate::nbt::{
+    Compound, NbtErr, Tag, BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN,
+};
+
+/// Converts a value to a tag. Structs and maps become compounds, sequences
+/// become lists, `None` fields are left out and booleans are bytes.
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<Tag, NbtErr> {
+    value
+        .serialize(TagSerializer)?
+        .ok_or_else(|| NbtErr::Message(String::from("Value has no NBT representation.")))
+}
+
+impl ser::Error for NbtErr {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtErr::Message(msg.to_string())
+    }
+}
+
+impl Serialize for Tag {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tag::Byte(v) => serializer.serialize_i8(*v),
+            Tag::Short(v) => serializer.serialize_i16(*v),
+            Tag::Int(v) => serializer.serialize_i32(*v),
+            Tag::Long(v) => serializer.serialize_i64(*v),
+            Tag::Float(v) => serializer.serialize_f32(*v),
+            Tag::Double(v) => serializer.serialize_f64(*v),
+            Tag::ByteArray(v) => serializer.serialize_newtype_struct(BYTE_ARRAY_TOKEN, v),
+            Tag::String(v) => serializer.serialize_str(v),
+            Tag::List(v) => serializer.collect_seq(v),
+            Tag::Compound(v) => serializer.collect_map(v),
+            Tag::IntArray(v) => serializer.serialize_newtype_struct(INT_ARRAY_TOKEN, v),
+            Tag::LongArray(v) => serializer.serialize_newtype_struct(LONG_ARRAY_TOKEN, v),
+        }
+    }
+}
+
+/// Serializes to `None` for missing values, which compounds leave out.
+struct TagSerializer;
+
+impl ser::Serializer for TagSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = VariantSerializer<ListSerializer>;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = VariantSerializer<CompoundSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::from(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    // NBT has no unsigned types, the bits are kept as they are.
+    fn serialize_u8(self, v: u8) -> Result<Option<Tag>, NbtErr> {
+        self.serialize_i8(v as i8)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Tag>, NbtErr> {
+        self.serialize_i16(v as i16)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Tag>, NbtErr> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Tag>, NbtErr> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::from(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|b| *b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<Tag>, NbtErr> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(Tag::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Option<Tag>, NbtErr> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Option<Tag>, NbtErr> {
+        let tag = value.serialize(self)?;
+        let tag = match (name, tag) {
+            (BYTE_ARRAY_TOKEN, Some(Tag::List(items))) => Tag::ByteArray(array(items, |item| match item {
+                Tag::Byte(v) => Some(v),
+                _ => None,
+            })),
+            (INT_ARRAY_TOKEN, Some(Tag::List(items))) => Tag::IntArray(array(items, |item| match item {
+                Tag::Int(v) => Some(v),
+                _ => None,
+            })),
+            (LONG_ARRAY_TOKEN, Some(Tag::List(items))) => Tag::LongArray(array(items, |item| match item {
+                Tag::Long(v) => Some(v),
+                _ => None,
+            })),
+            (_, tag) => return Ok(tag),
+        };
+        Ok(Some(tag))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Option<Tag>, NbtErr> {
+        match value.serialize(self)? {
+            Some(tag) => Ok(wrap_variant(variant, tag)),
+            None => Ok(Some(Tag::Compound(Compound::new()))),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, NbtErr> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, NbtErr> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListSerializer, NbtErr> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<ListSerializer>, NbtErr> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<CompoundSerializer, NbtErr> {
+        Ok(CompoundSerializer {
+            compound: Compound::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<CompoundSerializer, NbtErr> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<CompoundSerializer>, NbtErr> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct ListSerializer {
+    items: Vec<Tag>,
+}
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        let tag = value
+            .serialize(TagSerializer)?
+            .ok_or_else(|| NbtErr::Message(String::from("Lists cannot hold missing values.")))?;
+        if let Some(first) = self.items.first() {
+            if first.tag_type() != tag.tag_type() {
+                return Err(NbtErr::MixedList);
+            }
+        }
+        self.items.push(tag);
+        Ok(())
+    }
+
+    fn finish(self) -> Tag {
+        Tag::List(self.items)
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(self.finish()))
+    }
+}
+
+struct CompoundSerializer {
+    compound: Compound,
+    key: Option<String>,
+}
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), NbtErr> {
+        if let Some(tag) = value.serialize(TagSerializer)? {
+            self.compound.insert(String::from(key), tag);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Tag {
+        Tag::Compound(self.compound)
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), NbtErr> {
+        match key.serialize(TagSerializer)? {
+            Some(Tag::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(NbtErr::Message(String::from("Compound keys must be strings."))),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        let key = self.key.take().unwrap_or_default();
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(self.finish()))
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), NbtErr> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(Some(self.finish()))
+    }
+}
+
+/// Wraps the tag of an enum variant in a compound with the name of the variant.
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+fn wrap_variant(variant: &str, tag: Tag) -> Option<Tag> {
+    let mut compound = Compound::new();
+    compound.insert(String::from(variant), tag);
+    Some(Tag::Compound(compound))
+}
+
+/// Unwraps the elements of a list, which all have the type of the array.
+fn array<T>(items: Vec<Tag>, unwrap: impl Fn(Tag) -> Option<T>) -> Vec<T> {
+    items.into_iter().filter_map(unwrap).collect()
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<ListSerializer> {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtErr> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(wrap_variant(self.variant, self.inner.finish()))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<CompoundSerializer> {
+    type Ok = Option<Tag>;
+    type Error = NbtErr;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), NbtErr> {
+        self.inner.insert(key, value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, NbtErr> {
+        Ok(wrap_variant(self.variant, self.inner.finish()))
+    }
+}
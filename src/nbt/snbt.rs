@@ -0,0 +1,580 @@
+//! Stringified NBT, the `{Count:1b,id:"minecraft:stone"}` syntax of commands.
+// see https://minecraft.gamepedia.com/NBT_format#SNBT_format
+
+use thiserror::Error;
+
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use crate::nbt::io::MAX_DEPTH;
+use crate::nbt::{Compound, Tag};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind} at line {line}, column {column}")]
+pub struct SnbtErr {
+    pub kind: SnbtErrKind,
+    /// Byte offset of the error in the input.
+    pub position: usize,
+    /// 1-based line of the error.
+    pub line: usize,
+    /// 1-based column of the error, in characters.
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SnbtErrKind {
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Expected '{0}'")]
+    Expected(char),
+    #[error("Expected a key")]
+    ExpectedKey,
+    #[error("Expected a value")]
+    ExpectedValue,
+    #[error("Unterminated string")]
+    UnterminatedString,
+    #[error("Invalid escape sequence '\\{0}'")]
+    InvalidEscape(char),
+    #[error("Invalid array type '{0}'")]
+    InvalidArrayType(char),
+    #[error("Can't insert {found} into a list of {expected}")]
+    MixedList { expected: &'static str, found: &'static str },
+    #[error("Trailing data")]
+    TrailingData,
+    #[error("Lists and compounds are nested too deep")]
+    TooDeep,
+}
+
+/// Parses a tag written in SNBT.
+pub fn parse(input: &str) -> Result<Tag, SnbtErr> {
+    let mut parser = Parser {
+        input,
+        position: 0,
+        depth: 0,
+    };
+    let tag = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < input.len() {
+        return Err(parser.error(SnbtErrKind::TrailingData));
+    }
+    Ok(tag)
+}
+
+/// Prints a tag as compact SNBT, the way vanilla shows it in commands.
+pub fn to_string(tag: &Tag) -> String {
+    let mut result = String::new();
+    write_tag(&mut result, tag, None).unwrap();
+    result
+}
+
+/// Prints a tag as SNBT with one compound entry per line, indented by four spaces.
+pub fn to_string_pretty(tag: &Tag) -> String {
+    let mut result = String::new();
+    write_tag(&mut result, tag, Some(0)).unwrap();
+    result
+}
+
+impl FromStr for Tag {
+    type Err = SnbtErr;
+
+    fn from_str(s: &str) -> Result<Self, SnbtErr> {
+        parse(s)
+    }
+}
+
+/// Formats as compact SNBT, or pretty-printed SNBT with `{:#}`.
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_tag(f, self, if f.alternate() { Some(0) } else { None })
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    /// Lists and compounds the parser is in, at most `MAX_DEPTH` as for
+    /// binary NBT.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, kind: SnbtErrKind) -> SnbtErr {
+        self.error_at(kind, self.position)
+    }
+
+    fn error_at(&self, kind: SnbtErrKind, position: usize) -> SnbtErr {
+        let before = &self.input[..position];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        SnbtErr {
+            kind,
+            position,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    /// Skips whitespace and consumes `expected` if it comes next.
+    fn accept(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SnbtErr> {
+        if self.accept(expected) {
+            Ok(())
+        } else if self.peek().is_none() {
+            Err(self.error(SnbtErrKind::UnexpectedEnd))
+        } else {
+            Err(self.error(SnbtErrKind::Expected(expected)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Tag, SnbtErr> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') | Some('[') => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error(SnbtErrKind::TooDeep));
+                }
+                self.depth += 1;
+                let tag = if self.peek() == Some('{') {
+                    self.compound()
+                } else {
+                    self.list_or_array()
+                };
+                self.depth -= 1;
+                tag
+            }
+            Some('"') | Some('\'') => Ok(Tag::String(self.quoted_string()?)),
+            Some(_) => {
+                let start = self.position;
+                let token = self.unquoted_string();
+                if token.is_empty() {
+                    return Err(self.error_at(SnbtErrKind::ExpectedValue, start));
+                }
+                Ok(parse_primitive(token))
+            }
+            None => Err(self.error(SnbtErrKind::UnexpectedEnd)),
+        }
+    }
+
+    fn compound(&mut self) -> Result<Tag, SnbtErr> {
+        self.expect('{')?;
+        let mut compound = Compound::new();
+        if self.accept('}') {
+            return Ok(Tag::Compound(compound));
+        }
+        loop {
+            let key = self.key()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            compound.insert(key, value);
+            if !self.accept(',') {
+                break;
+            }
+        }
+        self.expect('}')?;
+        Ok(Tag::Compound(compound))
+    }
+
+    fn key(&mut self) -> Result<String, SnbtErr> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') | Some('\'') => self.quoted_string(),
+            Some(_) => {
+                let key = self.unquoted_string();
+                if key.is_empty() {
+                    return Err(self.error(SnbtErrKind::ExpectedKey));
+                }
+                Ok(String::from(key))
+            }
+            None => Err(self.error(SnbtErrKind::UnexpectedEnd)),
+        }
+    }
+
+    fn list_or_array(&mut self) -> Result<Tag, SnbtErr> {
+        self.expect('[')?;
+        let rest = &self.input[self.position..];
+        let mut chars = rest.chars();
+        if let (Some(kind), Some(';')) = (chars.next(), chars.next()) {
+            let kind_position = self.position;
+            self.position += kind.len_utf8() + 1;
+            return match kind {
+                'B' => self.array(Tag::ByteArray(Vec::new())),
+                'I' => self.array(Tag::IntArray(Vec::new())),
+                'L' => self.array(Tag::LongArray(Vec::new())),
+                kind => Err(self.error_at(SnbtErrKind::InvalidArrayType(kind), kind_position)),
+            };
+        }
+        let mut list: Vec<Tag> = Vec::new();
+        if self.accept(']') {
+            return Ok(Tag::List(list));
+        }
+        loop {
+            self.skip_whitespace();
+            let start = self.position;
+            let value = self.value()?;
+            if let Some(first) = list.first() {
+                if first.tag_type() != value.tag_type() {
+                    return Err(self.error_at(
+                        SnbtErrKind::MixedList {
+                            expected: type_name(first),
+                            found: type_name(&value),
+                        },
+                        start,
+                    ));
+                }
+            }
+            list.push(value);
+            if !self.accept(',') {
+                break;
+            }
+        }
+        self.expect(']')?;
+        Ok(Tag::List(list))
+    }
+
+    /// Reads the elements of `array`, which must all be of the array's element type.
+    fn array(&mut self, mut array: Tag) -> Result<Tag, SnbtErr> {
+        if self.accept(']') {
+            return Ok(array);
+        }
+        loop {
+            self.skip_whitespace();
+            let start = self.position;
+            let value = self.value()?;
+            let found = type_name(&value);
+            match (&mut array, value) {
+                (Tag::ByteArray(values), Tag::Byte(v)) => values.push(v),
+                (Tag::IntArray(values), Tag::Int(v)) => values.push(v),
+                (Tag::LongArray(values), Tag::Long(v)) => values.push(v),
+                (array, _) => {
+                    let expected = match array {
+                        Tag::ByteArray(_) => "TAG_Byte",
+                        Tag::IntArray(_) => "TAG_Int",
+                        _ => "TAG_Long",
+                    };
+                    return Err(self.error_at(SnbtErrKind::MixedList { expected, found }, start));
+                }
+            }
+            if !self.accept(',') {
+                break;
+            }
+        }
+        self.expect(']')?;
+        Ok(array)
+    }
+
+    fn quoted_string(&mut self) -> Result<String, SnbtErr> {
+        let start = self.position;
+        let quote = self.next().unwrap();
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                Some('\\') => {
+                    let escape_position = self.position;
+                    match self.next() {
+                        Some(c) if c == '\\' || c == quote => result.push(c),
+                        Some(c) => return Err(self.error_at(SnbtErrKind::InvalidEscape(c), escape_position)),
+                        None => return Err(self.error_at(SnbtErrKind::UnterminatedString, start)),
+                    }
+                }
+                Some(c) if c == quote => return Ok(result),
+                Some(c) => result.push(c),
+                None => return Err(self.error_at(SnbtErrKind::UnterminatedString, start)),
+            }
+        }
+    }
+
+    fn unquoted_string(&mut self) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(is_unquoted) {
+            self.next();
+        }
+        &self.input[start..self.position]
+    }
+}
+
+/// Characters allowed in keys and values without quotes.
+fn is_unquoted(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '+'
+}
+
+/// Turns an unquoted token into a number, boolean or string, as vanilla does.
+fn parse_primitive(token: &str) -> Tag {
+    match token {
+        "true" => return Tag::Byte(1),
+        "false" => return Tag::Byte(0),
+        _ => (),
+    }
+    let (number, suffix) = token.split_at(token.len() - 1);
+    let tag = match suffix {
+        "b" | "B" if is_integer(number) => number.parse().ok().map(Tag::Byte),
+        "s" | "S" if is_integer(number) => number.parse().ok().map(Tag::Short),
+        "l" | "L" if is_integer(number) => number.parse().ok().map(Tag::Long),
+        "f" | "F" if is_decimal(number) => number.parse().ok().map(Tag::Float),
+        "d" | "D" if is_decimal(number) => number.parse().ok().map(Tag::Double),
+        _ if is_integer(token) => token.parse().ok().map(Tag::Int),
+        _ if is_decimal(token) && token.contains('.') => token.parse().ok().map(Tag::Double),
+        _ => None,
+    };
+    tag.unwrap_or_else(|| Tag::from(token))
+}
+
+/// Matches `[-+]?(0|[1-9][0-9]*)`.
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(|c| c == '-' || c == '+').unwrap_or(s);
+    match digits.as_bytes() {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Matches `[-+]?([0-9]+[.]?|[0-9]*[.][0-9]+)(e[-+]?[0-9]+)?`.
+fn is_decimal(s: &str) -> bool {
+    let s = s.strip_prefix(|c| c == '-' || c == '+').unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (whole, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let mantissa_ok = digits(whole) && digits(fraction) && !(whole.is_empty() && fraction.is_empty());
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(|c| c == '-' || c == '+').unwrap_or(e);
+        !e.is_empty() && digits(e)
+    });
+    mantissa_ok && exponent_ok
+}
+
+fn type_name(tag: &Tag) -> &'static str {
+    match tag {
+        Tag::Byte(_) => "TAG_Byte",
+        Tag::Short(_) => "TAG_Short",
+        Tag::Int(_) => "TAG_Int",
+        Tag::Long(_) => "TAG_Long",
+        Tag::Float(_) => "TAG_Float",
+        Tag::Double(_) => "TAG_Double",
+        Tag::ByteArray(_) => "TAG_Byte_Array",
+        Tag::String(_) => "TAG_String",
+        Tag::List(_) => "TAG_List",
+        Tag::Compound(_) => "TAG_Compound",
+        Tag::IntArray(_) => "TAG_Int_Array",
+        Tag::LongArray(_) => "TAG_Long_Array",
+    }
+}
+
+fn write_string(out: &mut impl Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            out.write_char('\\')?;
+        }
+        out.write_char(c)?;
+    }
+    out.write_char('"')
+}
+
+fn write_key(out: &mut impl Write, key: &str) -> fmt::Result {
+    if !key.is_empty() && key.chars().all(is_unquoted) {
+        out.write_str(key)
+    } else {
+        write_string(out, key)
+    }
+}
+
+fn write_values<T>(
+    out: &mut impl Write,
+    prefix: &str,
+    values: &[T],
+    write: impl Fn(&mut dyn Write, &T) -> fmt::Result,
+) -> fmt::Result {
+    out.write_char('[')?;
+    out.write_str(prefix)?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.write_char(',')?;
+        }
+        write(&mut *out as &mut dyn Write, value)?;
+    }
+    out.write_char(']')
+}
+
+/// Writes `tag`, on several lines indented `indent` levels deep if given.
+fn write_tag(out: &mut impl Write, tag: &Tag, indent: Option<usize>) -> fmt::Result {
+    match tag {
+        Tag::Byte(v) => write!(out, "{}b", v),
+        Tag::Short(v) => write!(out, "{}s", v),
+        Tag::Int(v) => write!(out, "{}", v),
+        Tag::Long(v) => write!(out, "{}L", v),
+        Tag::Float(v) => write!(out, "{}f", v),
+        Tag::Double(v) => write!(out, "{}d", v),
+        Tag::ByteArray(values) => write_values(out, "B;", values, |out, v| write!(out, "{}b", v)),
+        Tag::String(s) => write_string(out, s),
+        Tag::IntArray(values) => write_values(out, "I;", values, |out, v| write!(out, "{}", v)),
+        Tag::LongArray(values) => write_values(out, "L;", values, |out, v| write!(out, "{}L", v)),
+        Tag::List(items) => {
+            let nested = items.iter().any(|item| matches!(item, Tag::List(_) | Tag::Compound(_)));
+            let indent = indent.filter(|_| nested);
+            out.write_char('[')?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                newline(out, indent.map(|indent| indent + 1))?;
+                write_tag(out, item, indent.map(|indent| indent + 1))?;
+            }
+            if !items.is_empty() {
+                newline(out, indent)?;
+            }
+            out.write_char(']')
+        }
+        Tag::Compound(compound) => {
+            out.write_char('{')?;
+            for (i, (key, item)) in compound.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                newline(out, indent.map(|indent| indent + 1))?;
+                write_key(out, key)?;
+                out.write_char(':')?;
+                if indent.is_some() {
+                    out.write_char(' ')?;
+                }
+                write_tag(out, item, indent.map(|indent| indent + 1))?;
+            }
+            if !compound.is_empty() {
+                newline(out, indent)?;
+            }
+            out.write_char('}')
+        }
+    }
+}
+
+/// Starts a new line indented `indent` levels deep, when pretty printing.
+fn newline(out: &mut impl Write, indent: Option<usize>) -> fmt::Result {
+    if let Some(indent) = indent {
+        out.write_char('\n')?;
+        for _ in 0..indent {
+            out.write_str("    ")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> (SnbtErrKind, usize, usize) {
+        let e = parse(input).unwrap_err();
+        (e.kind, e.line, e.column)
+    }
+
+    #[test]
+    fn parses_item() {
+        let tag = parse(r#"{Count:1b,id:"minecraft:stone",tag:{display:{Name:'{"text":"Rock"}'}}}"#).unwrap();
+        assert_eq!(tag.get("Count"), Some(&Tag::Byte(1)));
+        assert_eq!(tag.get("id"), Some(&Tag::from("minecraft:stone")));
+        let name = tag.get("tag").and_then(|t| t.get("display")).and_then(|t| t.get("Name"));
+        assert_eq!(name, Some(&Tag::from(r#"{"text":"Rock"}"#)));
+    }
+
+    #[test]
+    fn parses_primitives() {
+        assert_eq!(parse("-3s").unwrap(), Tag::Short(-3));
+        assert_eq!(parse("10").unwrap(), Tag::Int(10));
+        assert_eq!(parse("10L").unwrap(), Tag::Long(10));
+        assert_eq!(parse("1.5").unwrap(), Tag::Double(1.5));
+        assert_eq!(parse("1.5f").unwrap(), Tag::Float(1.5));
+        assert_eq!(parse("2d").unwrap(), Tag::Double(2.0));
+        assert_eq!(parse("1e3f").unwrap(), Tag::Float(1000.0));
+        assert_eq!(parse("true").unwrap(), Tag::Byte(1));
+        assert_eq!(parse("minecraft:stone").unwrap_err().kind, SnbtErrKind::TrailingData);
+        // out of range and malformed numbers are strings
+        assert_eq!(parse("300b").unwrap(), Tag::from("300b"));
+        assert_eq!(parse("012").unwrap(), Tag::from("012"));
+        assert_eq!(parse("1.2.3").unwrap(), Tag::from("1.2.3"));
+        assert_eq!(parse(r#""a\"b\\c""#).unwrap(), Tag::from(r#"a"b\c"#));
+    }
+
+    #[test]
+    fn parses_lists_and_arrays() {
+        assert_eq!(parse("[1, 2, 3]").unwrap(), Tag::List(vec![Tag::Int(1), Tag::Int(2), Tag::Int(3)]));
+        assert_eq!(parse("[]").unwrap(), Tag::List(Vec::new()));
+        assert_eq!(parse("[B; 1b, -1b]").unwrap(), Tag::ByteArray(vec![1, -1]));
+        assert_eq!(parse("[I;]").unwrap(), Tag::IntArray(Vec::new()));
+        assert_eq!(parse("[L;1L,2L]").unwrap(), Tag::LongArray(vec![1, 2]));
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error("{a:1,b:}"), (SnbtErrKind::ExpectedValue, 1, 8));
+        assert_eq!(error("{a:1\n b 2}"), (SnbtErrKind::Expected('}'), 2, 2));
+        assert_eq!(error("{a:1\n,b 2}"), (SnbtErrKind::Expected(':'), 2, 4));
+        assert_eq!(error(r#"{a:"oops}"#), (SnbtErrKind::UnterminatedString, 1, 4));
+        assert_eq!(error("[1,2b]"), (
+            SnbtErrKind::MixedList { expected: "TAG_Int", found: "TAG_Byte" },
+            1,
+            4,
+        ));
+        assert_eq!(error("[I;1,2L]"), (
+            SnbtErrKind::MixedList { expected: "TAG_Int", found: "TAG_Long" },
+            1,
+            6,
+        ));
+        assert_eq!(error("[X;1]"), (SnbtErrKind::InvalidArrayType('X'), 1, 2));
+        assert_eq!(error("{a:1"), (SnbtErrKind::UnexpectedEnd, 1, 5));
+        assert_eq!(error("{} {}"), (SnbtErrKind::TrailingData, 1, 4));
+        assert_eq!(error("{\"é\":'\\n'}"), (SnbtErrKind::InvalidEscape('n'), 1, 8));
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let deepest = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&deepest).is_ok());
+        assert_eq!(error(&"[".repeat(100_000)), (SnbtErrKind::TooDeep, 1, MAX_DEPTH + 1));
+        let compounds = format!("{}1", "{a:".repeat(MAX_DEPTH + 1));
+        assert_eq!(error(&compounds), (SnbtErrKind::TooDeep, 1, 3 * MAX_DEPTH + 1));
+    }
+
+    #[test]
+    fn prints_compact_and_pretty() {
+        let input = r#"{Count:1b,id:"minecraft:stone","with space":[{a:1.5f},{}],heights:[L;1L,2L],pos:[1d,-2.5d]}"#;
+        let tag = parse(input).unwrap();
+        assert_eq!(to_string(&tag), input);
+        assert_eq!(tag.to_string(), input);
+        let pretty = to_string_pretty(&tag);
+        assert_eq!(
+            pretty,
+            "{\n    Count: 1b,\n    id: \"minecraft:stone\",\n    \"with space\": [\n        {\n            a: 1.5f\n        },\n        {}\n    ],\n    heights: [L;1L,2L],\n    pos: [1d,-2.5d]\n}"
+        );
+        assert_eq!(format!("{:#}", tag), pretty);
+        assert_eq!(parse(&pretty).unwrap(), tag);
+    }
+}
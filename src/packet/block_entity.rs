@@ -0,0 +1,349 @@
+//! Block entities, the extra data of blocks such as the text of signs or the
+//! items of chests, kept as NBT in chunks.
+// see https://minecraft.gamepedia.com/Chunk_format#Block_entity_format
+
+use serde::{Deserialize, Serialize};
+
+use crate::nbt::{self, Compound, NbtErr, Tag};
+use crate::packet::builder::PacketBuilder;
+use crate::packet::play::clientbound::BlockEntityData;
+use crate::packet::types::{Position, RawBytes};
+
+/// A block entity at a position in the world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntity {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub kind: BlockEntityKind,
+    /// Tags its kind does not read, such as the skin of a player head, kept
+    /// to be saved and sent as they were read.
+    pub extra: Compound,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockEntityKind {
+    Sign(Sign),
+    Chest(Chest),
+    Banner(Banner),
+    Bed(Bed),
+    Skull(Skull),
+    /// Any other block entity, kept as it was read.
+    Other { id: String, data: Compound },
+}
+
+/// The four lines of a sign are JSON chat components.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Sign {
+    pub text1: String,
+    pub text2: String,
+    pub text3: String,
+    pub text4: String,
+    #[serde(default = "Sign::default_color")]
+    pub color: String,
+}
+
+impl Sign {
+    pub fn new(lines: [&str; 4]) -> Self {
+        let text = |line: &str| serde_json::json!({ "text": line }).to_string();
+        Self {
+            text1: text(lines[0]),
+            text2: text(lines[1]),
+            text3: text(lines[2]),
+            text4: text(lines[3]),
+            color: Self::default_color(),
+        }
+    }
+
+    fn default_color() -> String {
+        String::from("black")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Chest {
+    pub custom_name: Option<String>,
+    #[serde(default)]
+    pub items: Vec<ChestItem>,
+    pub lock: Option<String>,
+    pub loot_table: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChestItem {
+    #[serde(rename = "Slot")]
+    pub slot: i8,
+    pub id: String,
+    #[serde(rename = "Count")]
+    pub count: i8,
+    /// Everything else about the item, such as its damage, name or
+    /// enchantments.
+    pub tag: Option<Compound>,
+}
+
+/// The base color of a banner is the one of its block.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Banner {
+    pub custom_name: Option<String>,
+    #[serde(default)]
+    pub patterns: Vec<BannerPattern>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BannerPattern {
+    /// Short code of the pattern, such as `bs` for the bottom stripe.
+    pub pattern: String,
+    pub color: i32,
+}
+
+/// The color of a bed is the one of its block.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Bed {}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Skull {
+    pub owner: Option<SkullOwner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SkullOwner {
+    /// Hyphenated UUID of the player.
+    pub id: Option<String>,
+    pub name: Option<String>,
+}
+
+impl BlockEntityKind {
+    pub fn id(&self) -> &str {
+        match self {
+            BlockEntityKind::Sign(_) => "minecraft:sign",
+            BlockEntityKind::Chest(_) => "minecraft:chest",
+            BlockEntityKind::Banner(_) => "minecraft:banner",
+            BlockEntityKind::Bed(_) => "minecraft:bed",
+            BlockEntityKind::Skull(_) => "minecraft:skull",
+            BlockEntityKind::Other { id, .. } => id,
+        }
+    }
+
+    /// Returns the action Block Entity Data is sent with for this kind,
+    /// `None` for those clients do not need the data of.
+    pub fn action(&self) -> Option<u8> {
+        match self {
+            BlockEntityKind::Skull(_) => Some(4),
+            BlockEntityKind::Banner(_) => Some(6),
+            BlockEntityKind::Sign(_) => Some(9),
+            BlockEntityKind::Bed(_) => Some(11),
+            _ => None,
+        }
+    }
+}
+
+impl BlockEntity {
+    pub fn new(x: i32, y: i32, z: i32, kind: BlockEntityKind) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            kind,
+            extra: Compound::new(),
+        }
+    }
+
+    /// Returns the compound the block entity is saved and sent as.
+    pub fn to_tag(&self) -> Result<Tag, NbtErr> {
+        let data = match &self.kind {
+            BlockEntityKind::Sign(sign) => nbt::to_tag(sign)?,
+            BlockEntityKind::Chest(chest) => nbt::to_tag(chest)?,
+            BlockEntityKind::Banner(banner) => nbt::to_tag(banner)?,
+            BlockEntityKind::Bed(bed) => nbt::to_tag(bed)?,
+            BlockEntityKind::Skull(skull) => nbt::to_tag(skull)?,
+            BlockEntityKind::Other { data, .. } => Tag::Compound(data.clone()),
+        };
+        let mut compound = Compound::new();
+        compound.insert(String::from("id"), Tag::from(self.kind.id()));
+        compound.insert(String::from("x"), Tag::Int(self.x));
+        compound.insert(String::from("y"), Tag::Int(self.y));
+        compound.insert(String::from("z"), Tag::Int(self.z));
+        if let Tag::Compound(data) = data {
+            compound.extend(data);
+        }
+        merge(&mut compound, &self.extra);
+        Ok(Tag::Compound(compound))
+    }
+
+    pub fn from_tag(tag: Tag) -> Result<Self, NbtErr> {
+        let mut data = match tag {
+            Tag::Compound(compound) => compound,
+            _ => return Err(NbtErr::Message(String::from("Block entities must be compounds."))),
+        };
+        let mut int = |name: &str| match data.shift_remove(name) {
+            Some(Tag::Int(value)) => Ok(value),
+            _ => Err(NbtErr::Message(format!("Block entity has no {} coordinate.", name))),
+        };
+        let (x, y, z) = (int("x")?, int("y")?, int("z")?);
+        let id = match data.shift_remove("id") {
+            Some(Tag::String(id)) => id,
+            _ => return Err(NbtErr::Message(String::from("Block entity has no id."))),
+        };
+        let typed = Tag::Compound(data.clone());
+        let kind = match id.as_str() {
+            "minecraft:sign" => BlockEntityKind::Sign(nbt::from_tag(typed)?),
+            "minecraft:chest" => BlockEntityKind::Chest(nbt::from_tag(typed)?),
+            "minecraft:banner" => BlockEntityKind::Banner(nbt::from_tag(typed)?),
+            "minecraft:bed" => BlockEntityKind::Bed(nbt::from_tag(typed)?),
+            "minecraft:skull" => BlockEntityKind::Skull(nbt::from_tag(typed)?),
+            _ => return Ok(Self::new(x, y, z, BlockEntityKind::Other { id, data })),
+        };
+        let mut entity = Self::new(x, y, z, kind);
+        if let Tag::Compound(read) = entity.to_tag()? {
+            entity.extra = leftover(data, &read);
+        }
+        Ok(entity)
+    }
+
+    /// Returns the block entity as the unnamed compound sent in packets.
+    pub fn to_bytes(&self) -> Result<RawBytes, NbtErr> {
+        let mut builder = PacketBuilder::new();
+        nbt::write_tag(&mut builder, "", &self.to_tag()?)?;
+        Ok(RawBytes(builder.into_inner()))
+    }
+
+    /// Returns the Block Entity Data packet updating the block entity for
+    /// clients, `None` if clients do not need its data.
+    pub fn update_packet(&self) -> Result<Option<BlockEntityData>, NbtErr> {
+        let action = match self.kind.action() {
+            Some(action) => action,
+            None => return Ok(None),
+        };
+        Ok(Some(BlockEntityData {
+            location: Position {
+                x: self.x,
+                y: self.y,
+                z: self.z,
+            },
+            action,
+            nbt: self.to_bytes()?,
+        }))
+    }
+}
+
+/// Returns the tags of `tags` missing from `read`, looking into the compounds
+/// both have.
+fn leftover(tags: Compound, read: &Compound) -> Compound {
+    tags.into_iter()
+        .filter_map(|(name, tag)| match (tag, read.get(&name)) {
+            (tag, None) => Some((name, tag)),
+            (Tag::Compound(tags), Some(Tag::Compound(read))) => {
+                let tags = leftover(tags, read);
+                if tags.is_empty() {
+                    None
+                } else {
+                    Some((name, Tag::Compound(tags)))
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Adds the tags of `extra` missing from `tags`, looking into the compounds
+/// both have.
+fn merge(tags: &mut Compound, extra: &Compound) {
+    for (name, tag) in extra.iter() {
+        match (tags.get_mut(name), tag) {
+            (Some(Tag::Compound(tags)), Tag::Compound(extra)) => merge(tags, extra),
+            (Some(_), _) => (),
+            (None, tag) => {
+                tags.insert(name.clone(), tag.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_entities_round_trip() {
+        let sign = BlockEntity::new(-3, 70, 18, BlockEntityKind::Sign(Sign::new(["Hello", "", "", ""])));
+        let tag = sign.to_tag().unwrap();
+        assert_eq!(tag.get("id"), Some(&Tag::from("minecraft:sign")));
+        assert_eq!(tag.get("Text1"), Some(&Tag::from(r#"{"text":"Hello"}"#)));
+        assert_eq!(BlockEntity::from_tag(tag).unwrap(), sign);
+
+        let sword = nbt::snbt::parse(r#"{Damage:12,Enchantments:[{id:"minecraft:sharpness",lvl:2s}]}"#);
+        let sword = match sword.unwrap() {
+            Tag::Compound(compound) => compound,
+            _ => unreachable!(),
+        };
+        let chest = Chest {
+            items: vec![
+                ChestItem {
+                    slot: 3,
+                    id: String::from("minecraft:stone"),
+                    count: 64,
+                    tag: None,
+                },
+                ChestItem {
+                    slot: 4,
+                    id: String::from("minecraft:iron_sword"),
+                    count: 1,
+                    tag: Some(sword.clone()),
+                },
+            ],
+            ..Chest::default()
+        };
+        let chest = BlockEntity::new(0, 64, 0, BlockEntityKind::Chest(chest));
+        let tag = chest.to_tag().unwrap();
+        let items = match tag.get("Items") {
+            Some(Tag::List(items)) => items,
+            _ => panic!("Chests have a list of items."),
+        };
+        assert_eq!(items[0].get("tag"), None);
+        assert_eq!(items[1].get("tag"), Some(&Tag::Compound(sword)));
+        assert_eq!(BlockEntity::from_tag(tag).unwrap(), chest);
+        assert_eq!(chest.update_packet().unwrap(), None);
+
+        let furnace = nbt::snbt::parse(r#"{id:"minecraft:furnace",x:1,y:2,z:3,BurnTime:10s}"#).unwrap();
+        let entity = BlockEntity::from_tag(furnace.clone()).unwrap();
+        assert_eq!(entity.kind.id(), "minecraft:furnace");
+        assert_eq!(entity.to_tag().unwrap(), furnace);
+    }
+
+    #[test]
+    fn unread_tags_are_kept() {
+        let skull = r#"{id:"minecraft:skull",x:1,y:2,z:3,Owner:{Id:"069a79f4-44e9-4726-a5be-fca90e38aaf5",
+            Name:"Notch",Properties:{textures:[{Value:"eyJ0ZXh0dXJlcyI6e319"}]}}}"#;
+        let skull = nbt::snbt::parse(skull).unwrap();
+        let entity = BlockEntity::from_tag(skull.clone()).unwrap();
+        match &entity.kind {
+            BlockEntityKind::Skull(skull) => assert_eq!(skull.owner.as_ref().unwrap().name.as_deref(), Some("Notch")),
+            _ => panic!("Skulls are read as skulls."),
+        }
+        assert!(entity.extra["Owner"].get("Properties").is_some());
+        assert_eq!(entity.extra["Owner"].get("Name"), None);
+        assert_eq!(entity.to_tag().unwrap(), skull);
+
+        let chest = r#"{id:"minecraft:chest",x:0,y:64,z:0,Items:[],LootTable:"minecraft:chests/simple_dungeon",
+            LootTableSeed:42L}"#;
+        let chest = nbt::snbt::parse(chest).unwrap();
+        let entity = BlockEntity::from_tag(chest.clone()).unwrap();
+        assert_eq!(entity.extra.keys().collect::<Vec<_>>(), vec!["LootTableSeed"]);
+        assert_eq!(entity.to_tag().unwrap(), chest);
+    }
+
+    #[test]
+    fn signs_are_updated_with_action_9() {
+        let sign = BlockEntity::new(1, 2, 3, BlockEntityKind::Sign(Sign::new(["a", "b", "c", "d"])));
+        let packet = sign.update_packet().unwrap().unwrap();
+        assert_eq!(packet.action, 9);
+        assert_eq!(packet.location, Position { x: 1, y: 2, z: 3 });
+        assert_eq!(packet.nbt, sign.to_bytes().unwrap());
+    }
+}
@@ -1,3 +1,7 @@
+use flate2::write::ZlibEncoder;
+
+use std::io::Write;
+
 #[derive(Debug)]
 pub struct PacketBuilder {
     data: Vec<u8>,
@@ -14,8 +18,27 @@ impl PacketBuilder {
         Self { data: Vec::new() }
     }
 
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
     pub fn push_varint(&mut self, n: i32) {
-        let mut x = n;
+        let mut x = n as u32;
+        loop {
+            let mut temp = (x & 0b0111_1111) as u8;
+            x >>= 7;
+            if x != 0 {
+                temp |= 0b1000_0000;
+            }
+            self.data.push(temp);
+            if x == 0 {
+                break;
+            }
+        }
+    }
+
+    pub fn push_varlong(&mut self, n: i64) {
+        let mut x = n as u64;
         loop {
             let mut temp = (x & 0b0111_1111) as u8;
             x >>= 7;
@@ -102,6 +125,11 @@ impl PacketBuilder {
         self.data.extend(bytes.iter())
     }
 
+    /// Returns the pushed bytes without the packet length prefix.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
     pub fn build(self) -> Vec<u8> {
         let mut result = Vec::new();
         let mut x = self.data.len();
@@ -119,4 +147,28 @@ impl PacketBuilder {
         result.extend(self.data);
         result
     }
+
+    /// Builds the packet with the framing used after Set Compression.
+    // see https://wiki.vg/Protocol#With_compression
+    pub fn build_compressed(self, threshold: usize) -> Vec<u8> {
+        let mut body = PacketBuilder::new();
+        if self.data.len() >= threshold {
+            body.push_varint(self.data.len() as i32);
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&self.data).unwrap();
+            body.push_vec_u8(&encoder.finish().unwrap());
+        } else {
+            body.push_varint(0);
+            body.push_vec_u8(&self.data);
+        }
+        body.build()
+    }
+
+    /// Builds the packet compressed if a threshold is given.
+    pub fn build_with(self, threshold: Option<usize>) -> Vec<u8> {
+        match threshold {
+            Some(threshold) => self.build_compressed(threshold),
+            None => self.build(),
+        }
+    }
 }
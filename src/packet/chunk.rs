@@ -1,181 +1,694 @@
+use crate::biome::Biome;
+use crate::block::BlockState;
+use crate::packet::block_entity::BlockEntity;
+use crate::nbt::{self, Compound, IntArray, LongArray};
 use crate::packet::builder::PacketBuilder;
+use crate::packet::light::ChunkLight;
+use crate::packet::play::clientbound::ChunkData;
+use crate::packet::types::{RawBytes, VarInt};
+use crate::packet::version::ProtocolVersion;
 
-use color_eyre::{eyre::Report, eyre::WrapErr, Result, Section};
-use num_enum::TryFromPrimitive;
+use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
+/// Bits per block of the direct palette, enough for every global state id.
 pub const BIT_PER_BLOCK: u8 = 14;
+/// Fewest bits per block an indirect palette is sent with.
+pub const MIN_BITS_PER_BLOCK: u8 = 4;
+/// Most bits per block of an indirect palette, the direct palette is used above.
+pub const MAX_INDIRECT_BITS_PER_BLOCK: u8 = 8;
+
+const SECTION_VOLUME: usize = 16 * 16 * 16;
+/// Number of sections stacked in a chunk column, 256 blocks high.
+pub const SECTION_COUNT: usize = 16;
 
-#[derive(TryFromPrimitive)]
-#[repr(u16)]
-pub enum Block {
-    Air = 0x00,
-    Grass = 0x20,
-    Dirt = 0x30,
-}
 pub struct PrimaryBitMask;
 
+/// Longs holding the 256 heights of a heightmap.
+const HEIGHTMAP_LONG_COUNT: usize = 36;
+
+/// Heightmaps as sent in Chunk Data, 9 bits per column of blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct HeightMaps {
-    data: Vec<u8>,
+    motion_blocking: LongArray,
+    world_surface: LongArray,
 }
 
 impl HeightMaps {
-    pub fn new() -> Self {
-        let mut data = Vec::new();
-        let mut height_map = BitArray::new(9, 256);
-        for i in 0..256 {
-            height_map.set(i, 16);
-        }
-        let long_tag = String::from("MOTION_BLOCKING");
-        let long_tag = long_tag.as_bytes();
-        // Compound
-        data.push(10);
-        data.push(0);
-        data.push(0);
-        // Long array tag
-        data.push(12);
-        data.extend((long_tag.len() as u16).to_be_bytes().iter());
-        data.extend(long_tag);
-        // array size
-        data.extend(36_i32.to_be_bytes().iter());
-        for d in height_map.data.iter() {
-            data.extend(d.to_be_bytes().iter());
-        }
-        // End Compound
-        data.push(0);
-
+    pub fn new(column: &ChunkColumn) -> Self {
         Self {
-            data,
+            motion_blocking: column.motion_blocking.to_long_array(),
+            world_surface: column.world_surface.to_long_array(),
         }
     }
 }
 
-impl Default for HeightMaps {
-    fn default() -> Self {
-        Self::new()
+/// For each column of blocks, one more than the y of the highest block
+/// matching the heightmap, 0 if there is none.
+// see https://wiki.vg/Chunk_Format#Heightmaps
+pub struct Heightmap {
+    heights: [u16; 256],
+    matches: fn(BlockState) -> bool,
+}
+
+impl Heightmap {
+    /// Highest blocks stopping entities or holding fluids.
+    pub fn motion_blocking() -> Self {
+        Self {
+            heights: [0; 256],
+            matches: |block| block.blocks_motion() || block.has_fluid(),
+        }
+    }
+
+    /// Highest blocks which are not air.
+    pub fn world_surface() -> Self {
+        Self {
+            heights: [0; 256],
+            matches: |block| !block.is_air(),
+        }
+    }
+
+    pub fn height(&self, x: usize, z: usize) -> u16 {
+        self.heights[(z << 4) | x]
+    }
+
+    /// Updates the height of a column after the block at `y` became `block`.
+    fn update(&mut self, sections: &Sections, x: usize, y: usize, z: usize, block: BlockState) {
+        let height = self.height(x, z) as usize;
+        if (self.matches)(block) {
+            if y >= height {
+                self.heights[(z << 4) | x] = y as u16 + 1;
+            }
+        } else if y + 1 == height {
+            self.heights[(z << 4) | x] = self.highest(sections, x, y, z);
+        }
+    }
+
+    /// Returns the height of a column looking down from below `top`.
+    fn highest(&self, sections: &Sections, x: usize, top: usize, z: usize) -> u16 {
+        (0..top)
+            .rev()
+            .find(|y| (self.matches)(block_in(sections, x, *y, z)))
+            .map_or(0, |y| y as u16 + 1)
+    }
+
+    fn compute(&mut self, sections: &Sections) {
+        for z in 0..16 {
+            for x in 0..16 {
+                self.heights[(z << 4) | x] = self.highest(sections, x, SECTION_COUNT * 16, z);
+            }
+        }
     }
+
+    /// Reads heights written by `to_long_array`.
+    fn load(&mut self, array: &LongArray) {
+        let data = BitArray::from_raw(array.0.iter().map(|d| *d as u64).collect(), 9, 256);
+        for (index, height) in self.heights.iter_mut().enumerate() {
+            *height = data.get(index) as u16;
+        }
+    }
+
+    fn to_long_array(&self) -> LongArray {
+        let mut data = BitArray::new(9, 256);
+        for (index, height) in self.heights.iter().enumerate() {
+            data.set(index, *height as u64);
+        }
+        LongArray(data.data.iter().map(|d| *d as i64).collect())
+    }
+}
+
+/// Block entities of a chunk column, keyed by their position within it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BlockEntities {
+    entities: BTreeMap<(usize, usize, usize), BlockEntity>,
 }
 
+impl BlockEntities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the block entity at `y` in the world, `x` and `z` within the
+    /// column.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<&BlockEntity> {
+        self.entities.get(&(x, y, z))
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> Option<&mut BlockEntity> {
+        self.entities.get_mut(&(x, y, z))
+    }
+
+    /// Adds a block entity at its position, replacing the one already there.
+    pub fn insert(&mut self, entity: BlockEntity) -> Option<BlockEntity> {
+        let key = (entity.x.rem_euclid(16) as usize, entity.y as usize, entity.z.rem_euclid(16) as usize);
+        self.entities.insert(key, entity)
+    }
+
+    pub fn remove(&mut self, x: usize, y: usize, z: usize) -> Option<BlockEntity> {
+        self.entities.remove(&(x, y, z))
+    }
 
-pub struct BlockEntities;
+    pub fn iter(&self) -> impl Iterator<Item = &BlockEntity> {
+        self.entities.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Biomes of a chunk column, one for each cube of 4x4x4 blocks.
+// see https://wiki.vg/Chunk_Format#Biomes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "IntArray", try_from = "IntArray")]
 pub struct Biomes {
-    data: [i32; 1024],
+    data: [Biome; BIOME_COUNT],
 }
+
+/// Number of biomes of a chunk column, 4 by 4 over 64 layers.
+pub const BIOME_COUNT: usize = 1024;
+
 impl Biomes {
     pub fn new() -> Biomes {
-        Self { data: [21; 1024] }
+        Self::filled(Biome::Plains)
+    }
+
+    pub fn filled(biome: Biome) -> Biomes {
+        Self {
+            data: [biome; BIOME_COUNT],
+        }
+    }
+
+    /// Returns the biome of the block at `y` in the world, `x` and `z` within
+    /// the column.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Biome {
+        self.data[Self::index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, biome: Biome) {
+        self.data[Self::index(x, y, z)] = biome;
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        ((y >> 2) << 4) | ((z >> 2) << 2) | (x >> 2)
+    }
+
+    pub fn ids(&self) -> [i32; BIOME_COUNT] {
+        let mut ids = [0; BIOME_COUNT];
+        for (id, biome) in ids.iter_mut().zip(self.data.iter()) {
+            *id = biome.id();
+        }
+        ids
     }
 }
+
 impl Default for Biomes {
     fn default() -> Self {
         Self::new()
     }
 }
-pub struct ChunkPacket {
+
+impl From<Biomes> for IntArray {
+    fn from(biomes: Biomes) -> Self {
+        IntArray(biomes.ids().to_vec())
+    }
+}
+
+impl TryFrom<IntArray> for Biomes {
+    type Error = String;
+
+    fn try_from(ids: IntArray) -> Result<Self, String> {
+        if ids.0.len() != BIOME_COUNT {
+            return Err(format!("Expected {} biomes, found {}.", BIOME_COUNT, ids.0.len()));
+        }
+        let mut biomes = Biomes::new();
+        for (biome, id) in biomes.data.iter_mut().zip(ids.0.iter()) {
+            *biome = Biome::from_id(*id).ok_or_else(|| format!("Unknown biome id {}.", id))?;
+        }
+        Ok(biomes)
+    }
+}
+
+pub struct ChunkPacket<'a> {
     full_chunk: bool,
     primary_bit_mask: i32,
     height_maps: HeightMaps,
-    biomes: Biomes,
-    data: ChunkColumn,
-    block_entities: BlockEntities,
+    data: &'a ChunkColumn,
 }
 
-impl ChunkPacket {
-    pub fn new(data: ChunkColumn) -> ChunkPacket {
+impl<'a> ChunkPacket<'a> {
+    pub fn new(data: &'a ChunkColumn) -> ChunkPacket<'a> {
         Self {
             full_chunk: true,
-            primary_bit_mask: 0x1,
-            height_maps: HeightMaps::new(),
-            biomes: Biomes::new(),
+            primary_bit_mask: data.primary_bit_mask(),
+            height_maps: HeightMaps::new(data),
             data,
-            block_entities: BlockEntities,
         }
     }
 
-    pub fn build(self) -> Vec<u8> {
-        let mut builder = PacketBuilder::new();
-        builder.push_varint(0x22);
-        builder.push_int(self.data.location.0);
-        builder.push_int(self.data.location.1);
-        builder.push_bool(self.full_chunk);
-        builder.push_varint(self.primary_bit_mask);
-        builder.push_vec_u8(&self.height_maps.data);
-        builder.push_vec_i32(&self.biomes.data);
-        let mut data_size = 0;
-        for section in self.data.sections.iter() {
-            data_size += section.data.data.len() * 8;
-        }
-        builder.push_varint(data_size as i32);
-        for section in self.data.sections.iter() {
-            builder.push_vec_u64(&section.data.data);
-        }
-        builder.push_varint(0);
-        // builder.push entity
-        builder.build()
+    pub fn packet(self, version: ProtocolVersion) -> ChunkData {
+        let mut data = PacketBuilder::new();
+        for section in self.data.sections.iter().flatten() {
+            section.encode(&mut data);
+        }
+        if version < ProtocolVersion::V1_15 && self.full_chunk {
+            // one biome per column, taken from the lowest layer of the 4x4x4 biomes
+            for z in 0..16 {
+                for x in 0..16 {
+                    data.push_int(self.data.biomes.get(x, 0, z).id());
+                }
+            }
+        }
+        ChunkData {
+            chunk_x: self.data.location.0,
+            chunk_z: self.data.location.1,
+            full_chunk: self.full_chunk,
+            primary_bit_mask: VarInt(self.primary_bit_mask),
+            heightmaps: RawBytes(nbt::to_bytes("", &self.height_maps).expect("heightmaps are valid NBT")),
+            biomes: Box::new(self.data.biomes.ids()),
+            data: data.into_inner(),
+            block_entities: self
+                .data
+                .block_entities
+                .iter()
+                .map(|entity| entity.to_bytes().expect("block entities are valid NBT"))
+                .collect(),
+        }
     }
 }
 
 pub struct ChunkColumn {
     location: (i32, i32),
-    sections: Vec<ChunkSection>,
+    sections: Sections,
+    motion_blocking: Heightmap,
+    world_surface: Heightmap,
+    light: ChunkLight,
+    /// Whether `light` was computed or loaded, rather than left dark.
+    lit: bool,
+    biomes: Biomes,
+    block_entities: BlockEntities,
+    /// Tags of the saved `Level` compound which the server does not use,
+    /// such as entities and pending ticks, saved back as they were read.
+    extra_tags: Compound,
+    /// Whether the column changed since it was last saved.
+    dirty: bool,
 }
 
 impl ChunkColumn {
-    pub fn new(location: (i32, i32)) -> Self {
+    /// Returns a column holding only air.
+    pub fn empty(location: (i32, i32)) -> Self {
         Self {
             location,
-            sections: vec![grass_chunk_section()],
+            sections: Default::default(),
+            motion_blocking: Heightmap::motion_blocking(),
+            world_surface: Heightmap::world_surface(),
+            light: ChunkLight::new(),
+            lit: false,
+            biomes: Biomes::new(),
+            block_entities: BlockEntities::new(),
+            extra_tags: Compound::new(),
+            dirty: false,
         }
     }
-}
 
-fn grass_chunk_section() -> ChunkSection {
-    let mut section = ChunkSection::new();
-    for x in 0..15 {
-        for y in 0..15 {
-            for z in 0..15 {
-                section.set_block_at(x, y, z, Block::Grass);
-            }
+    pub fn location(&self) -> (i32, i32) {
+        self.location
+    }
+
+    /// Returns the block at `y` in the world, `x` and `z` within the column.
+    pub fn block_at(&self, x: usize, y: usize, z: usize) -> BlockState {
+        block_in(&self.sections, x, y, z)
+    }
+
+    /// Sets the block, dropping the block entity of the previous block if
+    /// its kind changes.
+    pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block: BlockState) {
+        let old_block = self.block_at(x, y, z);
+        if old_block == block {
+            return;
+        }
+        if old_block.kind() != block.kind() {
+            self.block_entities.remove(x, y, z);
+        }
+        self.dirty = true;
+        let slot = &mut self.sections[y >> 4];
+        if slot.is_none() && block.is_air() {
+            return;
+        }
+        let section = slot.get_or_insert_with(ChunkSection::new);
+        section.set_block_at(x, y & 15, z, block);
+        if section.is_empty() {
+            *slot = None;
+        }
+        self.motion_blocking.update(&self.sections, x, y, z, block);
+        self.world_surface.update(&self.sections, x, y, z, block);
+    }
+
+    /// Returns the light of the column, computed by `light::light_column`.
+    pub fn light(&self) -> &ChunkLight {
+        &self.light
+    }
+
+    /// Returns the light to change it, which marks the column as dirty.
+    pub fn light_mut(&mut self) -> &mut ChunkLight {
+        self.dirty = true;
+        &mut self.light
+    }
+
+    /// Replaces the light of the column, which then counts as lit and is
+    /// marked as dirty.
+    pub fn set_light(&mut self, light: ChunkLight) {
+        self.light = light;
+        self.lit = true;
+        self.dirty = true;
+    }
+
+    /// Returns whether the light of the column was computed or loaded.
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    pub fn biomes(&self) -> &Biomes {
+        &self.biomes
+    }
+
+    /// Returns the biomes to change them, which marks the column as dirty.
+    pub fn biomes_mut(&mut self) -> &mut Biomes {
+        self.dirty = true;
+        &mut self.biomes
+    }
+
+    pub fn block_entities(&self) -> &BlockEntities {
+        &self.block_entities
+    }
+
+    /// Returns the block entities to change them, which marks the column as
+    /// dirty.
+    pub fn block_entities_mut(&mut self) -> &mut BlockEntities {
+        self.dirty = true;
+        &mut self.block_entities
+    }
+
+    pub fn extra_tags(&self) -> &Compound {
+        &self.extra_tags
+    }
+
+    /// Replaces the extra tags, as read with the column.
+    pub fn set_extra_tags(&mut self, tags: Compound) {
+        self.extra_tags = tags;
+    }
+
+    /// Returns whether the column changed since it was loaded or last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Clears the dirty flag once the column is saved.
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn motion_blocking(&self) -> &Heightmap {
+        &self.motion_blocking
+    }
+
+    pub fn world_surface(&self) -> &Heightmap {
+        &self.world_surface
+    }
+
+    /// Returns the section at `y`, `None` if it holds only air.
+    pub fn section(&self, y: usize) -> Option<&ChunkSection> {
+        self.sections[y].as_ref()
+    }
+
+    /// Replaces the section at `y`, `None` or an empty section clearing it.
+    /// Heightmaps are left as they are.
+    pub fn set_section(&mut self, y: usize, section: Option<ChunkSection>) {
+        self.sections[y] = section.filter(|section| !section.is_empty());
+    }
+
+    /// Takes the heightmaps from saved ones, returns false and leaves them as
+    /// they are if either is malformed.
+    pub fn load_heightmaps(&mut self, heightmaps: &HeightMaps) -> bool {
+        let valid = [&heightmaps.motion_blocking, &heightmaps.world_surface]
+            .iter()
+            .all(|array| array.0.len() == HEIGHTMAP_LONG_COUNT);
+        if valid {
+            self.motion_blocking.load(&heightmaps.motion_blocking);
+            self.world_surface.load(&heightmaps.world_surface);
         }
+        valid
+    }
+
+    /// Computes the heightmaps from scratch, after sections were replaced.
+    pub fn compute_heightmaps(&mut self) {
+        self.motion_blocking.compute(&self.sections);
+        self.world_surface.compute(&self.sections);
+    }
+
+    /// Returns the mask of the sections sent in Chunk Data, bit 0 for the
+    /// bottom section.
+    pub fn primary_bit_mask(&self) -> i32 {
+        self.sections
+            .iter()
+            .enumerate()
+            .filter(|(_, section)| section.as_ref().is_some_and(|section| !section.is_empty()))
+            .fold(0, |mask, (y, _)| mask | 1 << y)
+    }
+}
+
+/// From the bottom up, `None` for sections holding only air.
+type Sections = [Option<ChunkSection>; SECTION_COUNT];
+
+fn block_in(sections: &Sections, x: usize, y: usize, z: usize) -> BlockState {
+    match &sections[y >> 4] {
+        Some(section) => section.block_at(x, y & 15, z),
+        None => BlockState::AIR,
     }
-    section
 }
 
 pub struct ChunkSection {
     block_count: u16,
-    bits_per_block: u8,
-    data: BitArray,
+    palette: Palette,
+}
+
+/// How the states of the 4096 blocks of a section are stored.
+// see https://wiki.vg/Chunk_Format#Palettes
+enum Palette {
+    /// Every block has the same state, no array is needed.
+    Single(u16),
+    /// Entries are indices into `states`, the states used by the section.
+    Indirect { states: Vec<u16>, data: BitArray },
+    /// Entries are global state ids.
+    Direct(BitArray),
 }
 
 impl ChunkSection {
     pub fn new() -> Self {
         ChunkSection {
             block_count: 0,
-            bits_per_block: BIT_PER_BLOCK,
-            data: BitArray::new(BIT_PER_BLOCK, 16 * 16 * 16),
+            palette: Palette::Single(BlockState::AIR.id()),
         }
     }
 
-    pub fn block_at(&self, x: usize, y: usize, z: usize) -> Result<Block> {
+    /// Creates a section from the palette and block states of a region file,
+    /// `None` if they do not describe 4096 blocks.
+    // see https://minecraft.gamepedia.com/Chunk_format#Block_format
+    pub fn from_palette(palette: &[BlockState], data: Vec<u64>) -> Option<Self> {
+        let bits = palette_bits(palette.len());
+        if palette.is_empty() || data.len() != BitArray::new(bits, SECTION_VOLUME).data.len() {
+            return None;
+        }
+        let data = BitArray::from_raw(data, bits, SECTION_VOLUME);
+        let mut block_count = 0;
+        for index in 0..SECTION_VOLUME {
+            let block = palette.get(data.get(index) as usize)?;
+            if !block.is_air() {
+                block_count += 1;
+            }
+        }
+        let states: Vec<u16> = palette.iter().map(|block| block.id()).collect();
+        let palette = if states.len() == 1 {
+            Palette::Single(states[0])
+        } else if bits > MAX_INDIRECT_BITS_PER_BLOCK {
+            let mut direct = BitArray::new(BIT_PER_BLOCK, SECTION_VOLUME);
+            for index in 0..SECTION_VOLUME {
+                direct.set(index, states[data.get(index) as usize] as u64);
+            }
+            Palette::Direct(direct)
+        } else {
+            Palette::Indirect { states, data }
+        };
+        Some(Self { block_count, palette })
+    }
+
+    /// Returns the palette and block states the section is saved with in
+    /// region files, as read by `from_palette`.
+    pub fn to_palette(&self) -> (Vec<BlockState>, Vec<u64>) {
+        let state = |id: u16| BlockState::from_id(id).expect("sections only hold valid states");
+        match &self.palette {
+            Palette::Single(single) => (
+                vec![state(*single)],
+                BitArray::new(MIN_BITS_PER_BLOCK, SECTION_VOLUME).data,
+            ),
+            Palette::Indirect { states, data } => {
+                let bits = palette_bits(states.len());
+                let data = if data.bits_per_value == bits { data.data.clone() } else { data.resized(bits).data };
+                (states.iter().map(|id| state(*id)).collect(), data)
+            }
+            Palette::Direct(data) => {
+                let mut states = Vec::new();
+                let entries: Vec<usize> = (0..SECTION_VOLUME)
+                    .map(|index| {
+                        let id = data.get(index) as u16;
+                        states.iter().position(|s| *s == id).unwrap_or_else(|| {
+                            states.push(id);
+                            states.len() - 1
+                        })
+                    })
+                    .collect();
+                let mut packed = BitArray::new(palette_bits(states.len()), SECTION_VOLUME);
+                for (index, entry) in entries.into_iter().enumerate() {
+                    packed.set(index, entry as u64);
+                }
+                (states.into_iter().map(state).collect(), packed.data)
+            }
+        }
+    }
+
+    /// Returns whether the section holds only air.
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+
+    pub fn block_at(&self, x: usize, y: usize, z: usize) -> BlockState {
+        BlockState::from_id(self.state_at(x, y, z)).expect("sections only hold valid states")
+    }
+
+    pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block: BlockState) {
+        let old_block = self.block_at(x, y, z);
+        if old_block == block {
+            return;
+        }
+        if block.is_air() && !old_block.is_air() {
+            self.block_count -= 1;
+        } else if old_block.is_air() && !block.is_air() {
+            self.block_count += 1;
+        }
         let index = (y << 8) | (z << 4) | x;
-        let block = self.data.get(index);
-        Block::try_from(block as u16).wrap_err(format!("Block ID: {:x} is not supproted.", block))
+        self.store(index, block.id());
     }
 
-    pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block: Block) {
+    /// Returns the global state id of a block.
+    fn state_at(&self, x: usize, y: usize, z: usize) -> u16 {
         let index = (y << 8) | (z << 4) | x;
-        let old_block = self.block_at(x, y, z).unwrap();
-        if let Block::Air = block {
-            match old_block {
-                Block::Air => (),
-                _ => self.block_count -= 1,
+        match &self.palette {
+            Palette::Single(state) => *state,
+            Palette::Indirect { states, data } => states[data.get(index) as usize],
+            Palette::Direct(data) => data.get(index) as u16,
+        }
+    }
+
+    fn store(&mut self, index: usize, state: u16) {
+        match &mut self.palette {
+            Palette::Single(single) => {
+                let mut data = BitArray::new(MIN_BITS_PER_BLOCK, SECTION_VOLUME);
+                data.set(index, 1);
+                self.palette = Palette::Indirect {
+                    states: vec![*single, state],
+                    data,
+                };
             }
-        } else if let Block::Air = old_block {
-            self.block_count += 1;
+            Palette::Indirect { states, data } => {
+                let entry = match states.iter().position(|s| *s == state) {
+                    Some(entry) => entry,
+                    None => {
+                        states.push(state);
+                        states.len() - 1
+                    }
+                };
+                if entry as u64 > data.highest_possible_value() {
+                    self.grow();
+                    self.store(index, state);
+                } else {
+                    data.set(index, entry as u64);
+                }
+            }
+            Palette::Direct(data) => data.set(index, state as u64),
         }
-        self.data.set(index, block as u64);
     }
+
+    /// Returns the number of bits per block the section is sent with.
+    pub fn bits_per_block(&self) -> u8 {
+        match &self.palette {
+            Palette::Single(_) => MIN_BITS_PER_BLOCK,
+            Palette::Indirect { data, .. } => data.bits_per_value,
+            Palette::Direct(_) => BIT_PER_BLOCK,
+        }
+    }
+
+    /// Adds a bit per block to an indirect palette, or switches to the direct
+    /// palette once more than `MAX_INDIRECT_BITS_PER_BLOCK` would be needed.
+    fn grow(&mut self) {
+        let (states, data) = match &self.palette {
+            Palette::Indirect { states, data } => (states, data),
+            _ => return,
+        };
+        let bits = data.bits_per_value + 1;
+        self.palette = if bits > MAX_INDIRECT_BITS_PER_BLOCK {
+            let mut direct = BitArray::new(BIT_PER_BLOCK, SECTION_VOLUME);
+            for index in 0..SECTION_VOLUME {
+                direct.set(index, states[data.get(index) as usize] as u64);
+            }
+            Palette::Direct(direct)
+        } else {
+            Palette::Indirect {
+                states: states.clone(),
+                data: data.resized(bits),
+            }
+        };
+    }
+
+    /// Writes the section as sent in Chunk Data.
+    pub fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_short(self.block_count as i16);
+        builder.push_byte(self.bits_per_block());
+        match &self.palette {
+            Palette::Single(state) => {
+                // 1.15 has no single state palette, send an indirect one full of zeros
+                builder.push_varint(1);
+                builder.push_varint(*state as i32);
+                let data = BitArray::new(MIN_BITS_PER_BLOCK, SECTION_VOLUME);
+                builder.push_varint(data.data.len() as i32);
+                builder.push_vec_u64(&data.data);
+            }
+            Palette::Indirect { states, data } => {
+                builder.push_varint(states.len() as i32);
+                for state in states.iter() {
+                    builder.push_varint(*state as i32);
+                }
+                builder.push_varint(data.data.len() as i32);
+                builder.push_vec_u64(&data.data);
+            }
+            Palette::Direct(data) => {
+                builder.push_varint(data.data.len() as i32);
+                builder.push_vec_u64(&data.data);
+            }
+        }
+    }
+}
+
+/// Bits per block of an indirect palette holding `len` states.
+fn palette_bits(len: usize) -> u8 {
+    let bits = (usize::BITS - len.saturating_sub(1).leading_zeros()) as u8;
+    bits.max(MIN_BITS_PER_BLOCK)
 }
 
 impl Default for ChunkSection {
@@ -297,4 +810,153 @@ impl BitArray {
     pub fn inner(&self) -> &Vec<u64> {
         &self.data
     }
+
+    /// Returns a copy of this `BitArray` with `bits_per_value` bits per value.
+    pub fn resized(&self, bits_per_value: u8) -> Self {
+        let mut result = Self::new(bits_per_value, self.capacity);
+        for index in 0..self.capacity {
+            result.set(index, self.get(index));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockKind;
+    use crate::packet::block_entity::{BlockEntityKind, Sign};
+    use crate::packet::reader::McBytesReader;
+
+    #[test]
+    fn palette_grows_then_becomes_direct() {
+        let mut section = ChunkSection::new();
+        assert_eq!(section.bits_per_block(), MIN_BITS_PER_BLOCK);
+        for state in 1..=16 {
+            section.set_block_at(state as usize, 0, 0, BlockState::from_id(state).unwrap());
+        }
+        assert_eq!(section.bits_per_block(), 5);
+        for state in 17..=256 {
+            let index = state as usize;
+            section.set_block_at(index & 15, index >> 8, (index >> 4) & 15, BlockState::from_id(state).unwrap());
+        }
+        assert_eq!(section.bits_per_block(), BIT_PER_BLOCK);
+        assert_eq!(section.block_count, 256);
+        for state in 1..=256 {
+            let index = state as usize;
+            assert_eq!(section.block_at(index & 15, index >> 8, (index >> 4) & 15).id(), state);
+        }
+        section.set_block_at(1, 0, 0, BlockState::AIR);
+        assert_eq!(section.block_count, 255);
+    }
+
+    #[test]
+    fn columns_only_send_non_empty_sections() {
+        let stone = BlockKind::Stone.default_state();
+        let mut column = ChunkColumn::empty((0, 0));
+        assert_eq!(column.primary_bit_mask(), 0);
+        column.set_block_at(3, 200, 5, stone);
+        column.set_block_at(0, 0, 0, stone);
+        assert_eq!(column.block_at(3, 200, 5), stone);
+        assert_eq!(column.block_at(3, 199, 5), BlockState::AIR);
+        assert_eq!(column.primary_bit_mask(), 1 << 12 | 1);
+
+        column.set_block_at(0, 0, 0, BlockState::AIR);
+        assert_eq!(column.primary_bit_mask(), 1 << 12);
+        let packet = ChunkPacket::new(&column).packet(ProtocolVersion::V1_15_2);
+        assert_eq!(packet.primary_bit_mask.0, 1 << 12);
+        let mut reader = McBytesReader::from_vec(packet.data);
+        assert_eq!(reader.read_short().unwrap(), 1);
+        assert_eq!(reader.read_unsigned_byte().unwrap(), MIN_BITS_PER_BLOCK);
+        assert_eq!(reader.read_varint().unwrap(), 2);
+        assert_eq!(reader.read_varint().unwrap(), BlockState::AIR.id() as i32);
+        assert_eq!(reader.read_varint().unwrap(), stone.id() as i32);
+        assert_eq!(reader.read_varint().unwrap(), 256);
+        assert_eq!(reader.remaining(), 256 * 8);
+    }
+
+    #[test]
+    fn heightmaps_follow_set_block_at() {
+        let stone = BlockKind::Stone.default_state();
+        let torch = BlockKind::Torch.default_state();
+        let mut column = ChunkColumn::empty((0, 0));
+        column.set_block_at(2, 10, 3, stone);
+        column.set_block_at(2, 40, 3, stone);
+        column.set_block_at(2, 41, 3, torch);
+        assert_eq!(column.motion_blocking().height(2, 3), 41);
+        assert_eq!(column.world_surface().height(2, 3), 42);
+        assert_eq!(column.world_surface().height(3, 2), 0);
+
+        column.set_block_at(2, 40, 3, BlockState::AIR);
+        assert_eq!(column.motion_blocking().height(2, 3), 11);
+        assert_eq!(column.world_surface().height(2, 3), 42);
+        column.set_block_at(2, 41, 3, BlockState::AIR);
+        assert_eq!(column.world_surface().height(2, 3), 11);
+
+        let heights = column.motion_blocking().heights;
+        column.compute_heightmaps();
+        assert_eq!(column.motion_blocking().heights, heights);
+    }
+
+    #[test]
+    fn biomes_are_sent_and_saved() {
+        let mut column = ChunkColumn::empty((0, 0));
+        column.biomes_mut().set(5, 70, 13, Biome::Desert);
+        assert_eq!(column.biomes().get(4, 68, 12), Biome::Desert);
+        assert_eq!(column.biomes().get(3, 70, 13), Biome::Plains);
+        let tag = nbt::to_tag(column.biomes()).unwrap();
+        assert_eq!(nbt::from_tag::<Biomes>(tag).unwrap(), *column.biomes());
+        assert!(nbt::from_tag::<Biomes>(nbt::Tag::IntArray(vec![1; 3])).is_err());
+        assert!(nbt::from_tag::<Biomes>(nbt::Tag::IntArray(vec![51; BIOME_COUNT])).is_err());
+
+        let packet = ChunkPacket::new(&column).packet(ProtocolVersion::V1_15_2);
+        assert_eq!(packet.biomes[(17 << 4) | (3 << 2) | 1], Biome::Desert.id());
+        assert_eq!(packet.biomes.iter().filter(|id| **id == Biome::Desert.id()).count(), 1);
+    }
+
+    #[test]
+    fn block_entities_are_sent_and_dropped_with_their_block() {
+        let mut column = ChunkColumn::empty((-1, 2));
+        let sign = BlockEntity::new(-3, 70, 40, BlockEntityKind::Sign(Sign::new(["Hello", "", "", ""])));
+        column.set_block_at(13, 70, 8, BlockKind::OakSign.default_state());
+        column.block_entities_mut().insert(sign.clone());
+        assert_eq!(column.block_entities().get(13, 70, 8), Some(&sign));
+
+        column.set_block_at(13, 70, 8, BlockKind::OakSign.default_state().with("rotation", "4").unwrap());
+        assert_eq!(column.block_entities().len(), 1);
+        let packet = ChunkPacket::new(&column).packet(ProtocolVersion::V1_15_2);
+        assert_eq!(packet.block_entities, vec![sign.to_bytes().unwrap()]);
+
+        let mut column = ChunkColumn::empty((-1, 2));
+        column.set_block_at(13, 70, 8, BlockKind::OakSign.default_state());
+        column.block_entities_mut().insert(sign);
+        column.set_block_at(13, 70, 8, BlockState::AIR);
+        assert!(column.block_entities().is_empty());
+    }
+
+    #[test]
+    fn sections_are_encoded_as_the_protocol_expects() {
+        let mut section = ChunkSection::new();
+        let mut builder = PacketBuilder::new();
+        section.encode(&mut builder);
+        let mut reader = McBytesReader::from_vec(builder.into_inner());
+        assert_eq!(reader.read_short().unwrap(), 0);
+        assert_eq!(reader.read_unsigned_byte().unwrap(), MIN_BITS_PER_BLOCK);
+        assert_eq!(reader.read_varint().unwrap(), 1);
+        assert_eq!(reader.read_varint().unwrap(), BlockState::AIR.id() as i32);
+        assert_eq!(reader.read_varint().unwrap(), 256);
+
+        section.set_block_at(0, 0, 0, BlockKind::GrassBlock.default_state());
+        let mut builder = PacketBuilder::new();
+        section.encode(&mut builder);
+        let mut reader = McBytesReader::from_vec(builder.into_inner());
+        assert_eq!(reader.read_short().unwrap(), 1);
+        assert_eq!(reader.read_unsigned_byte().unwrap(), MIN_BITS_PER_BLOCK);
+        assert_eq!(reader.read_varint().unwrap(), 2);
+        assert_eq!(reader.read_varint().unwrap(), BlockState::AIR.id() as i32);
+        assert_eq!(reader.read_varint().unwrap(), BlockKind::GrassBlock.default_state().id() as i32);
+        assert_eq!(reader.read_varint().unwrap(), 256);
+        assert_eq!(reader.read_long().unwrap(), 1);
+        assert_eq!(reader.remaining(), 255 * 8);
+    }
 }
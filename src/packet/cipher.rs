@@ -0,0 +1,171 @@
+use aes::Aes128;
+use cfb8::cipher::{generic_array::GenericArray, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::ready;
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+type Aes128Cfb8Enc = cfb8::Encryptor<Aes128>;
+type Aes128Cfb8Dec = cfb8::Decryptor<Aes128>;
+
+/// Shared secret of a connection, shared between its reading and writing
+/// `CipherStream`s to switch both of them at once.
+#[derive(Debug, Clone, Default)]
+pub struct Encryption {
+    secret: Arc<Mutex<Option<[u8; 16]>>>,
+}
+
+impl Encryption {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypts everything read or written from now on with the given secret.
+    pub fn enable(&self, secret: [u8; 16]) {
+        *self.secret.lock().unwrap() = Some(secret);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.secret.lock().unwrap().is_some()
+    }
+
+    fn secret(&self) -> Option<[u8; 16]> {
+        *self.secret.lock().unwrap()
+    }
+}
+
+/// AES-128-CFB8 layer around a byte stream, passing bytes through
+/// untouched until its `Encryption` is enabled.
+// see https://wiki.vg/Protocol_Encryption
+pub struct CipherStream<S> {
+    inner: S,
+    encryption: Encryption,
+    encryptor: Option<Aes128Cfb8Enc>,
+    decryptor: Option<Aes128Cfb8Dec>,
+    /// Encrypted bytes which are not written to `inner` yet.
+    pending: Vec<u8>,
+}
+
+impl<S> CipherStream<S> {
+    pub fn new(inner: S, encryption: Encryption) -> Self {
+        Self {
+            inner,
+            encryption,
+            encryptor: None,
+            decryptor: None,
+            pending: Vec::new(),
+        }
+    }
+
+    fn encryptor(&mut self) -> Option<&mut Aes128Cfb8Enc> {
+        if self.encryptor.is_none() {
+            let secret = self.encryption.secret()?;
+            self.encryptor = Some(Aes128Cfb8Enc::new(&secret.into(), &secret.into()));
+        }
+        self.encryptor.as_mut()
+    }
+
+    fn decryptor(&mut self) -> Option<&mut Aes128Cfb8Dec> {
+        if self.decryptor.is_none() {
+            let secret = self.encryption.secret()?;
+            self.decryptor = Some(Aes128Cfb8Dec::new(&secret.into(), &secret.into()));
+        }
+        self.decryptor.as_mut()
+    }
+}
+
+impl<S: AsyncWrite + Unpin> CipherStream<S> {
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CipherStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        if let Some(decryptor) = self.decryptor() {
+            for byte in buf[..n].chunks_mut(1) {
+                decryptor.decrypt_block_mut(GenericArray::from_mut_slice(byte));
+            }
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CipherStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if !self.encryption.is_enabled() {
+            return Pin::new(&mut self.inner).poll_write(cx, buf);
+        }
+        ready!(self.poll_write_pending(cx))?;
+        // The cipher state advances with every byte, so the whole buffer
+        // is taken at once and written out on later polls if needed.
+        let mut data = buf.to_vec();
+        let encryptor = self.encryptor().unwrap();
+        for byte in data.chunks_mut(1) {
+            encryptor.encrypt_block_mut(GenericArray::from_mut_slice(byte));
+        }
+        self.pending = data;
+        if let Poll::Ready(Err(e)) = self.poll_write_pending(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx))?;
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, io::Cursor, AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn round_trip() {
+        let secret = [42u8; 16];
+        let message = b"hello, encrypted world".to_vec();
+
+        let encryption = Encryption::new();
+        let mut writer = CipherStream::new(Cursor::new(Vec::new()), encryption.clone());
+        block_on(writer.write_all(b"plain")).unwrap();
+        encryption.enable(secret);
+        block_on(writer.write_all(&message)).unwrap();
+        block_on(writer.flush()).unwrap();
+        let written = writer.inner.into_inner();
+        assert_eq!(&written[..5], b"plain");
+        assert_ne!(&written[5..], message.as_slice());
+
+        let encryption = Encryption::new();
+        encryption.enable(secret);
+        let mut reader = CipherStream::new(Cursor::new(written[5..].to_vec()), encryption);
+        let mut read = Vec::new();
+        block_on(reader.read_to_end(&mut read)).unwrap();
+        assert_eq!(read, message);
+    }
+}
@@ -1,48 +1,383 @@
 use async_codec::{Decode, DecodeResult, Encode, EncodeResult};
+use flate2::read::ZlibDecoder;
+use thiserror::Error;
+
+use crate::packet::builder::PacketBuilder;
+
+use std::io::Read;
+use std::sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc,
+};
 
 pub use async_codec::Framed;
 
-pub struct McCodec;
+/// First byte of a pre-Netty (1.6 and older) server list ping.
+pub const LEGACY_PING: u8 = 0xFE;
+
+/// Largest frame vanilla accepts, the most a 3-byte VarInt can describe.
+pub const DEFAULT_MAX_PACKET_LENGTH: usize = 2097151;
+
+#[derive(Debug, Clone, Copy, Error)]
+pub enum CodecErr {
+    #[error("Varint is too big.")]
+    VarintTooBig,
+    #[error("Negative length: {0}")]
+    NegativeLength(i32),
+    #[error("Packet of {0} bytes is over the maximum length.")]
+    PacketTooBig(usize),
+    #[error("Compressed packet has an invalid data length: {0}")]
+    InvalidDataLength(usize),
+    #[error("Could not decompress the packet.")]
+    Decompression,
+}
+
+/// Item read from or written to a `Framed<_, McCodec>`.
+#[derive(Debug)]
+pub enum Frame {
+    /// Packet id and data, framed for the compression setting of the connection.
+    Packet(Vec<u8>),
+    /// A legacy server list ping, only ever produced as the first frame of a connection.
+    LegacyPing,
+    /// Bytes written as they are, such as the answer to a legacy ping.
+    Raw(Vec<u8>),
+}
+
+/// Compression threshold of a connection.
+///
+/// `Framed` owns its codec, so the handle is shared between the codec
+/// and the code writing packets to switch both framings at once.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    threshold: Arc<AtomicI32>,
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self {
+            threshold: Arc::new(AtomicI32::new(-1)),
+        }
+    }
+
+    /// Returns the threshold if compression is enabled.
+    pub fn threshold(&self) -> Option<usize> {
+        let threshold = self.threshold.load(Ordering::SeqCst);
+        if threshold < 0 {
+            None
+        } else {
+            Some(threshold as usize)
+        }
+    }
+
+    pub fn enable(&self, threshold: usize) {
+        self.threshold.store(threshold as i32, Ordering::SeqCst);
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct McCodec {
+    first_frame: bool,
+    compression: Compression,
+    max_packet_length: usize,
+    /// Frame that did not fit in the buffer given to the last `encode`.
+    pending: Option<Vec<u8>>,
+}
+
+impl McCodec {
+    pub fn new() -> Self {
+        Self::with_compression(Compression::new())
+    }
+
+    pub fn with_compression(compression: Compression) -> Self {
+        Self {
+            first_frame: true,
+            compression,
+            max_packet_length: DEFAULT_MAX_PACKET_LENGTH,
+            pending: None,
+        }
+    }
+
+    /// Rejects frames whose length, before or after decompression, is over `length` bytes.
+    pub fn with_max_packet_length(mut self, length: usize) -> Self {
+        self.max_packet_length = length;
+        self
+    }
+}
+
+impl Default for McCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Encode for McCodec {
-    type Item = Vec<u8>;
-    type Error = ();
+    type Item = Frame;
+    type Error = CodecErr;
 
-    fn encode(&mut self, _item: &Vec<u8>, _buf: &mut [u8]) -> EncodeResult<()> {
-        Ok(1).into()
+    fn encode(&mut self, item: &Frame, buf: &mut [u8]) -> EncodeResult<CodecErr> {
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => match item {
+                Frame::Packet(data) => {
+                    PacketBuilder::from_vec(data.clone()).build_with(self.compression.threshold())
+                }
+                Frame::Raw(data) => data.clone(),
+                Frame::LegacyPing => return EncodeResult::Ok(0),
+            },
+        };
+        if frame.len() > buf.len() {
+            let length = frame.len();
+            self.pending = Some(frame);
+            return EncodeResult::Overflow(length);
+        }
+        buf[..frame.len()].copy_from_slice(&frame);
+        EncodeResult::Ok(frame.len())
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
     }
 }
 
 impl Decode for McCodec {
-    type Item = Vec<u8>;
-    type Error = ();
+    type Item = Frame;
+    type Error = CodecErr;
 
-    fn decode(&mut self, buf: &mut [u8]) -> (usize, DecodeResult<Vec<u8>, ()>) {
-        let (header_length, packet_length) = get_packet_length(buf);
-        let total_length = header_length + packet_length as usize;
+    fn decode(&mut self, buf: &mut [u8]) -> (usize, DecodeResult<Frame, CodecErr>) {
+        let threshold = self.compression.threshold();
+        if self.first_frame {
+            self.first_frame = false;
+            // A handshake length can never start with 0xFE, so this must be
+            // a legacy client. The rest of its payload is not needed.
+            if threshold.is_none() && buf.first() == Some(&LEGACY_PING) {
+                return (buf.len(), Ok(Frame::LegacyPing).into());
+            }
+        }
+        let (header_length, packet_length) = match get_length(buf, self.max_packet_length) {
+            Ok(Some(length)) => length,
+            Ok(None) => return (0, DecodeResult::UnexpectedEnd),
+            Err(e) => return (buf.len(), Err(e).into()),
+        };
+        let total_length = header_length + packet_length;
         if total_length > buf.len() {
             return (0, DecodeResult::UnexpectedEnd);
         }
-        let result = Vec::from(&buf[header_length..]);
-        (total_length, Ok(result).into())
+        let body = &buf[header_length..total_length];
+        let result = match threshold {
+            Some(threshold) => decompress(body, threshold, self.max_packet_length),
+            None => Ok(Vec::from(body)),
+        };
+        (total_length, result.map(Frame::Packet).into())
     }
 }
 
-fn get_packet_length(header: &[u8]) -> (usize, i32) {
-    let mut num_read: i32 = 0;
+/// Unpacks the "data length / data" body of a compressed frame.
+// see https://wiki.vg/Protocol#With_compression
+fn decompress(body: &[u8], threshold: usize, max_length: usize) -> Result<Vec<u8>, CodecErr> {
+    let (header_length, data_length) = get_length(body, max_length)?
+        .ok_or(CodecErr::InvalidDataLength(0))?;
+    let data = &body[header_length..];
+    if data_length == 0 {
+        return Ok(Vec::from(data));
+    }
+    if data_length < threshold {
+        return Err(CodecErr::InvalidDataLength(data_length));
+    }
+    let mut result = Vec::with_capacity(data_length);
+    // one byte more than announced is enough to notice a lying header
+    ZlibDecoder::new(data)
+        .take(data_length as u64 + 1)
+        .read_to_end(&mut result)
+        .map_err(|_| CodecErr::Decompression)?;
+    if result.len() != data_length {
+        return Err(CodecErr::InvalidDataLength(data_length));
+    }
+    Ok(result)
+}
+
+/// Reads the VarInt length in front of `header`, returning its size in bytes and
+/// its value, or `None` if more bytes are needed.
+fn get_length(header: &[u8], max_length: usize) -> Result<Option<(usize, usize)>, CodecErr> {
+    let mut num_read = 0;
     let mut result: i32 = 0;
-    let mut header = header.iter();
-    loop {
-        let x = header.next().unwrap();
-        let value = x & 0b01111111;
-        result |= (value as i32) << (7 * num_read);
-        num_read += 1;
-        if num_read > 5 {
-            panic!("VarInt is too big");
+    for x in header.iter() {
+        if num_read == 5 {
+            return Err(CodecErr::VarintTooBig);
         }
+        result |= ((x & 0b01111111) as i32) << (7 * num_read);
+        num_read += 1;
         if (x & 0b10000000) == 0 {
-            break;
+            if result < 0 {
+                return Err(CodecErr::NegativeLength(result));
+            }
+            if result as usize > max_length {
+                return Err(CodecErr::PacketTooBig(result as usize));
+            }
+            return Ok(Some((num_read, result as usize)));
+        }
+    }
+    if num_read == 5 {
+        return Err(CodecErr::VarintTooBig);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::builder::PacketBuilder;
+
+    fn packet(id: i32, payload_size: usize) -> PacketBuilder {
+        let mut builder = PacketBuilder::new();
+        builder.push_varint(id);
+        for i in 0..payload_size {
+            builder.push_byte(i as u8);
+        }
+        builder
+    }
+
+    fn decode_all(codec: &mut McCodec, mut buf: Vec<u8>) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while !buf.is_empty() {
+            let (consumed, result) = codec.decode(&mut buf);
+            match result {
+                DecodeResult::Ok(Frame::Packet(data)) => frames.push(data),
+                _ => panic!("failed to decode frame"),
+            }
+            buf.drain(..consumed);
+        }
+        frames
+    }
+
+    #[test]
+    fn round_trip_uncompressed() {
+        let mut buf = packet(0x00, 10).build();
+        buf.extend(packet(0x22, 300).build());
+        let frames = decode_all(&mut McCodec::new(), buf);
+        assert_eq!(frames, vec![packet(0x00, 10).into_inner(), packet(0x22, 300).into_inner()]);
+    }
+
+    #[test]
+    fn round_trip_compressed() {
+        let compression = Compression::new();
+        compression.enable(256);
+        let big = packet(0x22, 70_000).build_compressed(256);
+        assert!(big.len() < 70_000);
+        let mut buf = packet(0x00, 10).build_compressed(256);
+        buf.extend(big);
+        let frames = decode_all(&mut McCodec::with_compression(compression), buf);
+        assert_eq!(frames, vec![packet(0x00, 10).into_inner(), packet(0x22, 70_000).into_inner()]);
+    }
+
+    #[test]
+    fn below_threshold_is_sent_raw() {
+        let buf = packet(0x01, 10).build_compressed(256);
+        // packet length, data length of 0, then the raw packet
+        assert_eq!(buf[1], 0);
+        assert_eq!(&buf[2..], packet(0x01, 10).into_inner().as_slice());
+    }
+
+    fn decode_err(codec: &mut McCodec, mut buf: Vec<u8>) -> Option<CodecErr> {
+        match codec.decode(&mut buf).1 {
+            DecodeResult::Err(e) => Some(e),
+            DecodeResult::UnexpectedEnd => None,
+            DecodeResult::Ok(frame) => panic!("decoded {:?}", frame),
         }
     }
-    (num_read as usize, result)
+
+    #[test]
+    fn malformed_lengths_are_errors() {
+        let mut codec = McCodec::new();
+        assert!(decode_err(&mut codec, vec![]).is_none());
+        assert!(decode_err(&mut codec, vec![0x80, 0x80]).is_none());
+        assert!(matches!(
+            decode_err(&mut codec, vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x01]),
+            Some(CodecErr::VarintTooBig)
+        ));
+        assert!(matches!(
+            decode_err(&mut codec, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+            Some(CodecErr::NegativeLength(-1))
+        ));
+        assert!(matches!(
+            decode_err(&mut codec.with_max_packet_length(100), vec![101, 0]),
+            Some(CodecErr::PacketTooBig(101))
+        ));
+    }
+
+    #[test]
+    fn lying_data_length_is_an_error() {
+        let compression = Compression::new();
+        compression.enable(256);
+        let mut codec = McCodec::with_compression(compression);
+        // the header says 300 bytes, the zlib body holds 70000
+        let mut buf = packet(0x22, 70_000).build_compressed(256);
+        let (header, _) = get_length(&buf, usize::MAX).unwrap().unwrap();
+        let (data_header, _) = get_length(&buf[header..], usize::MAX).unwrap().unwrap();
+        let mut body = PacketBuilder::new();
+        body.push_varint(300);
+        body.push_vec_u8(&buf[header + data_header..]);
+        buf = body.build();
+        assert!(matches!(decode_err(&mut codec, buf), Some(CodecErr::InvalidDataLength(300))));
+        // a non-zero data length below the threshold
+        let mut body = PacketBuilder::new();
+        body.push_varint(10);
+        body.push_vec_u8(&[0; 10]);
+        assert!(matches!(decode_err(&mut codec, body.build()), Some(CodecErr::InvalidDataLength(10))));
+    }
+
+    fn encode(codec: &mut McCodec, frame: Frame) -> Vec<u8> {
+        codec.reset();
+        let mut buf = Vec::new();
+        loop {
+            match codec.encode(&frame, &mut buf) {
+                EncodeResult::Ok(length) => {
+                    buf.truncate(length);
+                    return buf;
+                }
+                EncodeResult::Overflow(length) => buf.resize(length, 0),
+                EncodeResult::Err(e) => panic!("failed to encode frame: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let compression = Compression::new();
+        let mut codec = McCodec::with_compression(compression.clone());
+        let mut buf = encode(&mut codec, Frame::Packet(packet(0x00, 10).into_inner()));
+        assert_eq!(buf, packet(0x00, 10).build());
+        compression.enable(256);
+        buf.extend(encode(&mut codec, Frame::Packet(packet(0x22, 70_000).into_inner())));
+        buf.extend(encode(&mut codec, Frame::Packet(packet(0x01, 10).into_inner())));
+        let frames = decode_all(&mut McCodec::new(), buf.drain(..12).collect());
+        assert_eq!(frames, vec![packet(0x00, 10).into_inner()]);
+        let frames = decode_all(&mut McCodec::with_compression(compression), buf);
+        assert_eq!(frames, vec![packet(0x22, 70_000).into_inner(), packet(0x01, 10).into_inner()]);
+    }
+
+    #[test]
+    fn raw_frames_are_written_as_they_are() {
+        let mut codec = McCodec::new();
+        assert_eq!(encode(&mut codec, Frame::Raw(vec![0xFF, 0x00, 0x01])), vec![0xFF, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn legacy_ping_is_only_read_first() {
+        let mut codec = McCodec::new();
+        let (consumed, result) = codec.decode(&mut [0xFE, 0x01]);
+        assert_eq!(consumed, 2);
+        assert!(matches!(result, DecodeResult::Ok(Frame::LegacyPing)));
+
+        // after the first frame, 0xFE starts the length of a packet
+        let mut codec = McCodec::new();
+        assert_eq!(decode_all(&mut codec, packet(0x00, 1).build()), vec![packet(0x00, 1).into_inner()]);
+        let (consumed, result) = codec.decode(&mut [0xFE, 0x01]);
+        assert_eq!(consumed, 0);
+        assert!(matches!(result, DecodeResult::UnexpectedEnd));
+    }
 }
@@ -0,0 +1,15 @@
+pub mod serverbound {
+    use crate::packet::types::VarInt;
+    use crate::packets;
+
+    packets! {
+        serverbound ServerboundHandshaking in Handshaking {
+            0x00 => Handshake {
+                protocol_version: VarInt,
+                server_address: String,
+                server_port: u16,
+                next_state: VarInt,
+            },
+        }
+    }
+}
@@ -0,0 +1,626 @@
+//! Sky and block light. Light spreads from the sky and from emissive blocks
+//! with a flood fill crossing section and chunk borders, and is relit
+//! incrementally when a block changes.
+// see https://wiki.vg/Chunk_Format#Lighting
+
+use crate::block::BlockState;
+use crate::packet::chunk::{ChunkColumn, SECTION_COUNT};
+use crate::packet::play::clientbound::{LightArrays, UpdateLight};
+use crate::packet::types::VarInt;
+
+use std::collections::{HashMap, VecDeque};
+
+/// Sections light is kept for, the column plus one section below and above.
+pub const LIGHT_SECTION_COUNT: usize = SECTION_COUNT + 2;
+pub const MAX_LIGHT: u8 = 15;
+const LIGHT_ARRAY_LENGTH: usize = 2048;
+/// Lowest y light is kept for, inclusive.
+const MIN_Y: i32 = -16;
+/// Highest y light is kept for, exclusive.
+const MAX_Y: i32 = (SECTION_COUNT as i32 + 1) * 16;
+
+/// Neighbours of a block, the last one being the block below.
+const DIRECTIONS: [(i32, i32, i32); 6] = [(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1), (0, 1, 0), (0, -1, 0)];
+const DOWN: (i32, i32, i32) = (0, -1, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+/// Light levels of the 4096 blocks of a section, two per byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightArray(Vec<u8>);
+
+impl LightArray {
+    pub fn new() -> Self {
+        Self(vec![0; LIGHT_ARRAY_LENGTH])
+    }
+
+    /// Wraps the 2048 bytes of a light array as saved in region files,
+    /// `None` if there are not as many.
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        if bytes.len() == LIGHT_ARRAY_LENGTH {
+            Some(Self(bytes))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        let index = (y << 8) | (z << 4) | x;
+        (self.0[index >> 1] >> ((index & 1) * 4)) & 0xf
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = (y << 8) | (z << 4) | x;
+        let shift = (index & 1) * 4;
+        self.0[index >> 1] = (self.0[index >> 1] & !(0xf << shift)) | (level << shift);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+
+    pub fn inner(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl Default for LightArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sky and block light of a chunk column, from the section below the world
+/// to the section above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkLight {
+    sky: Vec<LightArray>,
+    block: Vec<LightArray>,
+}
+
+impl ChunkLight {
+    pub fn new() -> Self {
+        Self {
+            sky: vec![LightArray::new(); LIGHT_SECTION_COUNT],
+            block: vec![LightArray::new(); LIGHT_SECTION_COUNT],
+        }
+    }
+
+    fn arrays(&self, kind: LightKind) -> &Vec<LightArray> {
+        match kind {
+            LightKind::Sky => &self.sky,
+            LightKind::Block => &self.block,
+        }
+    }
+
+    /// Returns the light at `y` in the world, from -16 to 271.
+    pub fn get(&self, kind: LightKind, x: usize, y: i32, z: usize) -> u8 {
+        let section = ((y - MIN_Y) >> 4) as usize;
+        self.arrays(kind)[section].get(x, (y & 15) as usize, z)
+    }
+
+    fn set(&mut self, kind: LightKind, x: usize, y: i32, z: usize, level: u8) {
+        let section = ((y - MIN_Y) >> 4) as usize;
+        let arrays = match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        };
+        arrays[section].set(x, (y & 15) as usize, z, level);
+    }
+
+    /// Returns the light of the section at `section_y`, from -1 below the
+    /// world to 16 above it.
+    pub fn section(&self, kind: LightKind, section_y: i32) -> &LightArray {
+        &self.arrays(kind)[(section_y + 1) as usize]
+    }
+
+    pub fn set_section(&mut self, kind: LightKind, section_y: i32, array: LightArray) {
+        let arrays = match kind {
+            LightKind::Sky => &mut self.sky,
+            LightKind::Block => &mut self.block,
+        };
+        arrays[(section_y + 1) as usize] = array;
+    }
+
+    /// Returns the Update Light packet of the column, sections without any
+    /// light are sent as empty.
+    pub fn packet(&self, location: (i32, i32)) -> UpdateLight {
+        let (sky_light_mask, empty_sky_light_mask, sky_light) = light_masks(&self.sky);
+        let (block_light_mask, empty_block_light_mask, block_light) = light_masks(&self.block);
+        UpdateLight {
+            chunk_x: VarInt(location.0),
+            chunk_z: VarInt(location.1),
+            sky_light_mask: VarInt(sky_light_mask),
+            block_light_mask: VarInt(block_light_mask),
+            empty_sky_light_mask: VarInt(empty_sky_light_mask),
+            empty_block_light_mask: VarInt(empty_block_light_mask),
+            sky_light,
+            block_light,
+        }
+    }
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn light_masks(arrays: &[LightArray]) -> (i32, i32, LightArrays) {
+    let mut mask = 0;
+    let mut empty_mask = 0;
+    let mut sent = Vec::new();
+    for (index, array) in arrays.iter().enumerate() {
+        if array.is_empty() {
+            empty_mask |= 1 << index;
+        } else {
+            mask |= 1 << index;
+            sent.push(array.inner().clone());
+        }
+    }
+    (mask, empty_mask, LightArrays(sent))
+}
+
+/// Chunk columns light can spread between, usually those loaded in a world.
+pub trait Columns {
+    fn column(&self, location: (i32, i32)) -> Option<&ChunkColumn>;
+    fn column_mut(&mut self, location: (i32, i32)) -> Option<&mut ChunkColumn>;
+}
+
+impl Columns for HashMap<(i32, i32), ChunkColumn> {
+    fn column(&self, location: (i32, i32)) -> Option<&ChunkColumn> {
+        self.get(&location)
+    }
+
+    fn column_mut(&mut self, location: (i32, i32)) -> Option<&mut ChunkColumn> {
+        self.get_mut(&location)
+    }
+}
+
+/// A column on its own, lit without the columns around it.
+impl Columns for ChunkColumn {
+    fn column(&self, location: (i32, i32)) -> Option<&ChunkColumn> {
+        Some(self).filter(|column| column.location() == location)
+    }
+
+    fn column_mut(&mut self, location: (i32, i32)) -> Option<&mut ChunkColumn> {
+        Some(self).filter(|column| column.location() == location)
+    }
+}
+
+type BlockPos = (i32, i32, i32);
+
+/// Returns the block at a position in the world, `None` if its column is not loaded.
+fn block_at<C: Columns + ?Sized>(columns: &C, (x, y, z): BlockPos) -> Option<BlockState> {
+    let column = columns.column((x >> 4, z >> 4))?;
+    if y < 0 || y >= SECTION_COUNT as i32 * 16 {
+        return Some(BlockState::AIR);
+    }
+    Some(column.block_at((x & 15) as usize, y as usize, (z & 15) as usize))
+}
+
+/// Returns the light at a position in the world, `None` if its column is not loaded.
+fn light_at<C: Columns + ?Sized>(columns: &C, kind: LightKind, (x, y, z): BlockPos) -> Option<u8> {
+    let column = columns.column((x >> 4, z >> 4))?;
+    Some(column.light().get(kind, (x & 15) as usize, y, (z & 15) as usize))
+}
+
+fn set_light_at<C: Columns + ?Sized>(columns: &mut C, kind: LightKind, (x, y, z): BlockPos, level: u8) {
+    if let Some(column) = columns.column_mut((x >> 4, z >> 4)) {
+        column.light_mut().set(kind, (x & 15) as usize, y, (z & 15) as usize, level);
+    }
+}
+
+/// Returns the light reaching a block from a neighbour at `level`, sky light
+/// at its maximum going down through transparent blocks without losing any.
+fn spread(kind: LightKind, level: u8, direction: BlockPos, block: BlockState) -> u8 {
+    let opacity = block.light_opacity();
+    if kind == LightKind::Sky && direction == DOWN && level == MAX_LIGHT && opacity == 0 {
+        return MAX_LIGHT;
+    }
+    level.saturating_sub(opacity.max(1))
+}
+
+fn offset((x, y, z): BlockPos, (dx, dy, dz): BlockPos) -> Option<BlockPos> {
+    let y = y + dy;
+    if (MIN_Y..MAX_Y).contains(&y) {
+        Some((x + dx, y, z + dz))
+    } else {
+        None
+    }
+}
+
+/// Spreads light from every block of `queue` to its neighbours.
+fn increase<C: Columns + ?Sized>(columns: &mut C, kind: LightKind, mut queue: VecDeque<BlockPos>) {
+    while let Some(pos) = queue.pop_front() {
+        let level = light_at(columns, kind, pos).unwrap_or(0);
+        if level <= 1 {
+            continue;
+        }
+        for direction in DIRECTIONS.iter() {
+            let neighbour = match offset(pos, *direction) {
+                Some(neighbour) => neighbour,
+                None => continue,
+            };
+            let block = match block_at(columns, neighbour) {
+                Some(block) => block,
+                None => continue,
+            };
+            let new = spread(kind, level, *direction, block);
+            if new > light_at(columns, kind, neighbour).unwrap_or(MAX_LIGHT) {
+                set_light_at(columns, kind, neighbour, new);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Darkens the blocks lit through `pos`, returning the lit blocks bordering
+/// the darkened area, which light has to spread back from.
+fn decrease<C: Columns + ?Sized>(columns: &mut C, kind: LightKind, pos: BlockPos) -> VecDeque<BlockPos> {
+    let mut relight = VecDeque::new();
+    let level = light_at(columns, kind, pos).unwrap_or(0);
+    set_light_at(columns, kind, pos, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back((pos, level));
+    while let Some((pos, level)) = queue.pop_front() {
+        for direction in DIRECTIONS.iter() {
+            let neighbour = match offset(pos, *direction) {
+                Some(neighbour) => neighbour,
+                None => continue,
+            };
+            let current = match light_at(columns, kind, neighbour) {
+                Some(current) if current > 0 => current,
+                _ => continue,
+            };
+            let from_sky = kind == LightKind::Sky && *direction == DOWN && level == MAX_LIGHT;
+            if current < level || (from_sky && current == MAX_LIGHT) {
+                set_light_at(columns, kind, neighbour, 0);
+                queue.push_back((neighbour, current));
+            } else {
+                relight.push_back(neighbour);
+            }
+        }
+    }
+    relight
+}
+
+/// Returns the lowest y the sky reaches straight down at `x`, `z`, `None`
+/// if the column is not loaded.
+fn sky_top<C: Columns + ?Sized>(columns: &C, x: i32, z: i32) -> Option<i32> {
+    let column = columns.column((x >> 4, z >> 4))?;
+    let (x, z) = ((x & 15) as usize, (z & 15) as usize);
+    let top = (0..SECTION_COUNT * 16)
+        .rev()
+        .find(|y| column.block_at(x, *y, z).light_opacity() != 0)
+        .map_or(MIN_Y, |y| y as i32 + 1);
+    Some(top)
+}
+
+/// Lights a column from scratch, spreading light to and from the loaded
+/// columns around it.
+pub fn light_column<C: Columns + ?Sized>(columns: &mut C, location: (i32, i32)) {
+    let (base_x, base_z) = (location.0 * 16, location.1 * 16);
+    // tops of the column and of the blocks around it
+    let mut tops = [[None; 18]; 18];
+    for (i, row) in tops.iter_mut().enumerate() {
+        for (j, top) in row.iter_mut().enumerate() {
+            *top = sky_top(columns, base_x + j as i32 - 1, base_z + i as i32 - 1);
+        }
+    }
+    let column = match columns.column_mut(location) {
+        Some(column) => column,
+        None => return,
+    };
+    let mut light = ChunkLight::new();
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+    for z in 0..16 {
+        for x in 0..16 {
+            let top = tops[z + 1][x + 1].unwrap_or(MIN_Y);
+            for y in top..MAX_Y {
+                light.set(LightKind::Sky, x, y, z, MAX_LIGHT);
+            }
+            // light only spreads sideways below the sky of a neighbour, and
+            // down from the top into blocks which let some of it through
+            let highest_neighbour = [tops[z][x + 1], tops[z + 2][x + 1], tops[z + 1][x], tops[z + 1][x + 2]]
+                .iter()
+                .flatten()
+                .max()
+                .copied()
+                .unwrap_or(MIN_Y);
+            for y in top..highest_neighbour.max(top + 1) {
+                sky.push_back((base_x + x as i32, y, base_z + z as i32));
+            }
+            for y in 0..SECTION_COUNT * 16 {
+                let emission = column.block_at(x, y, z).light_emission();
+                if emission > 0 {
+                    light.set(LightKind::Block, x, y as i32, z, emission);
+                    block.push_back((base_x + x as i32, y as i32, base_z + z as i32));
+                }
+            }
+        }
+    }
+    column.set_light(light);
+    // light already spread in the neighbours flows back in, sky light only
+    // below the sky of the block next to it
+    for i in 0..16 {
+        let borders = [
+            ((base_x - 1, base_z + i), tops[i as usize + 1][1]),
+            ((base_x + 16, base_z + i), tops[i as usize + 1][16]),
+            ((base_x + i, base_z - 1), tops[1][i as usize + 1]),
+            ((base_x + i, base_z + 16), tops[16][i as usize + 1]),
+        ];
+        for ((x, z), inner_top) in borders.iter() {
+            for y in MIN_Y..MAX_Y {
+                let pos = (*x, y, *z);
+                if y < inner_top.unwrap_or(MIN_Y) && light_at(columns, LightKind::Sky, pos).unwrap_or(0) > 1 {
+                    sky.push_back(pos);
+                }
+                if light_at(columns, LightKind::Block, pos).unwrap_or(0) > 1 {
+                    block.push_back(pos);
+                }
+            }
+        }
+    }
+    increase(columns, LightKind::Sky, sky);
+    increase(columns, LightKind::Block, block);
+}
+
+/// Spreads light both ways across the borders of a column lit on its own,
+/// once it is among the loaded columns around it. Lighting a column with
+/// `light_column` on its own then its borders gives the same light as
+/// lighting it among its neighbours, only the borders need them.
+pub fn light_borders<C: Columns + ?Sized>(columns: &mut C, location: (i32, i32)) {
+    let mut raised = Vec::new();
+    for &(dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)].iter() {
+        let neighbour_location = (location.0 + dx, location.1 + dz);
+        let (column, neighbour) = match (columns.column(location), columns.column(neighbour_location)) {
+            (Some(column), Some(neighbour)) => (column, neighbour),
+            _ => continue,
+        };
+        for i in 0..16 {
+            // the blocks on both sides of the border, within their columns
+            let (inside, outside) = match (dx, dz) {
+                (1, 0) => ((15, i), (0, i)),
+                (-1, 0) => ((0, i), (15, i)),
+                (0, 1) => ((i, 15), (i, 0)),
+                _ => ((i, 0), (i, 15)),
+            };
+            for y in MIN_Y..MAX_Y {
+                for kind in [LightKind::Sky, LightKind::Block].iter() {
+                    if let Some(level) = crossing(*kind, (column, inside), (neighbour, outside), y) {
+                        raised.push((*kind, world_pos(neighbour_location, outside, y), level));
+                    }
+                    if let Some(level) = crossing(*kind, (neighbour, outside), (column, inside), y) {
+                        raised.push((*kind, world_pos(location, inside, y), level));
+                    }
+                }
+            }
+        }
+    }
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+    for (kind, pos, level) in raised.into_iter() {
+        // blocks in corners may be raised across two borders
+        if level > light_at(columns, kind, pos).unwrap_or(MAX_LIGHT) {
+            set_light_at(columns, kind, pos, level);
+            match kind {
+                LightKind::Sky => sky.push_back(pos),
+                LightKind::Block => block.push_back(pos),
+            }
+        }
+    }
+    increase(columns, LightKind::Sky, sky);
+    increase(columns, LightKind::Block, block);
+}
+
+/// Returns the light spreading sideways from a block of a column to the
+/// block next to it in another column, if brighter than the light there.
+fn crossing(
+    kind: LightKind,
+    (from, (from_x, from_z)): (&ChunkColumn, (usize, usize)),
+    (to, (to_x, to_z)): (&ChunkColumn, (usize, usize)),
+    y: i32,
+) -> Option<u8> {
+    let level = from.light().get(kind, from_x, y, from_z);
+    if level <= 1 {
+        return None;
+    }
+    let block = if (0..SECTION_COUNT as i32 * 16).contains(&y) {
+        to.block_at(to_x, y as usize, to_z)
+    } else {
+        BlockState::AIR
+    };
+    // only light going down is special, the direction is otherwise unused
+    let new = spread(kind, level, (1, 0, 0), block);
+    Some(new).filter(|new| *new > to.light().get(kind, to_x, y, to_z))
+}
+
+fn world_pos(location: (i32, i32), (x, z): (usize, usize), y: i32) -> BlockPos {
+    (location.0 * 16 + x as i32, y, location.1 * 16 + z as i32)
+}
+
+/// Sets a block at a position in the world and relights around it. Returns
+/// `false` if its column is not loaded or `y` is outside of the world.
+pub fn set_block<C: Columns + ?Sized>(columns: &mut C, (x, y, z): BlockPos, block: BlockState) -> bool {
+    if !(0..SECTION_COUNT as i32 * 16).contains(&y) {
+        return false;
+    }
+    match columns.column_mut((x >> 4, z >> 4)) {
+        Some(column) => column.set_block_at((x & 15) as usize, y as usize, (z & 15) as usize, block),
+        None => return false,
+    }
+    for kind in [LightKind::Sky, LightKind::Block].iter() {
+        let mut relight = decrease(columns, *kind, (x, y, z));
+        for direction in DIRECTIONS.iter() {
+            if let Some(neighbour) = offset((x, y, z), *direction) {
+                relight.push_back(neighbour);
+            }
+        }
+        if *kind == LightKind::Block && block.light_emission() > 0 {
+            set_light_at(columns, *kind, (x, y, z), block.light_emission());
+            relight.push_back((x, y, z));
+        }
+        increase(columns, *kind, relight);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockKind;
+
+    /// Two columns side by side with a stone floor at y 0 to 3.
+    fn floor() -> HashMap<(i32, i32), ChunkColumn> {
+        let stone = BlockKind::Stone.default_state();
+        let mut columns = HashMap::new();
+        for &location in [(0, 0), (1, 0)].iter() {
+            let mut column = ChunkColumn::empty(location);
+            for z in 0..16 {
+                for x in 0..16 {
+                    for y in 0..4 {
+                        column.set_block_at(x, y, z, stone);
+                    }
+                }
+            }
+            columns.insert(location, column);
+            light_column(&mut columns, location);
+        }
+        columns
+    }
+
+    /// Copies the blocks of the columns, leaving them unlit.
+    fn unlit(columns: &HashMap<(i32, i32), ChunkColumn>) -> HashMap<(i32, i32), ChunkColumn> {
+        let mut fresh = HashMap::new();
+        for (location, column) in columns.iter() {
+            let mut copy = ChunkColumn::empty(*location);
+            for z in 0..16 {
+                for x in 0..16 {
+                    for y in 0..SECTION_COUNT * 16 {
+                        copy.set_block_at(x, y, z, column.block_at(x, y, z));
+                    }
+                }
+            }
+            fresh.insert(*location, copy);
+        }
+        fresh
+    }
+
+    fn relit(columns: &HashMap<(i32, i32), ChunkColumn>) -> HashMap<(i32, i32), ChunkColumn> {
+        let mut fresh = unlit(columns);
+        let locations: Vec<_> = fresh.keys().copied().collect();
+        for location in locations {
+            light_column(&mut fresh, location);
+        }
+        fresh
+    }
+
+    #[test]
+    fn sky_light_stops_at_the_ground() {
+        let columns = floor();
+        assert_eq!(light_at(&columns, LightKind::Sky, (5, 4, 5)), Some(15));
+        assert_eq!(light_at(&columns, LightKind::Sky, (5, 3, 5)), Some(0));
+        assert_eq!(light_at(&columns, LightKind::Sky, (5, 271, 5)), Some(15));
+        assert_eq!(light_at(&columns, LightKind::Sky, (5, -16, 5)), Some(0));
+        let packet = columns[&(0, 0)].light().packet((0, 0));
+        assert_eq!(packet.empty_sky_light_mask.0, 1);
+        assert_eq!(packet.sky_light_mask.0, 0b11_1111_1111_1111_1110);
+        assert_eq!(packet.sky_light.0.len(), 17);
+        assert_eq!(packet.block_light_mask.0, 0);
+    }
+
+    #[test]
+    fn sky_light_dims_through_water_and_leaves() {
+        let mut columns = floor();
+        for x in 0..32 {
+            for z in 0..16 {
+                for y in 4..8 {
+                    set_block(&mut columns, (x, y, z), BlockKind::Water.default_state());
+                }
+            }
+        }
+        set_block(&mut columns, (5, 9, 5), BlockKind::OakLeaves.default_state());
+        set_block(&mut columns, (5, 8, 5), BlockKind::OakLeaves.default_state());
+        let levels = |columns: &HashMap<_, _>, (x, z)| -> Vec<_> {
+            (4..10).rev().map(|y| light_at(columns, LightKind::Sky, (x, y, z)).unwrap()).collect()
+        };
+        assert_eq!(levels(&columns, (20, 8)), vec![15, 15, 14, 13, 12, 11]);
+        assert_eq!(levels(&columns, (5, 5)), vec![14, 14, 13, 12, 11, 10]);
+
+        let fresh = relit(&columns);
+        assert_eq!(levels(&fresh, (20, 8)), vec![15, 15, 14, 13, 12, 11]);
+        for location in [(0, 0), (1, 0)].iter() {
+            assert!(columns[location].light() == fresh[location].light());
+        }
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_borders() {
+        let mut columns = floor();
+        let torch = BlockKind::Torch.default_state();
+        assert!(set_block(&mut columns, (15, 4, 8), torch));
+        assert_eq!(light_at(&columns, LightKind::Block, (15, 4, 8)), Some(14));
+        assert_eq!(light_at(&columns, LightKind::Block, (16, 4, 8)), Some(13));
+        assert_eq!(light_at(&columns, LightKind::Block, (20, 5, 8)), Some(8));
+        assert_eq!(light_at(&columns, LightKind::Block, (15, 3, 8)), Some(0));
+        assert!(!set_block(&mut columns, (40, 4, 8), torch));
+
+        assert!(set_block(&mut columns, (15, 4, 8), BlockState::AIR));
+        assert_eq!(light_at(&columns, LightKind::Block, (16, 4, 8)), Some(0));
+        assert!(columns.values().all(|column| column.light().block.iter().all(LightArray::is_empty)));
+    }
+
+    #[test]
+    fn incremental_light_matches_lighting_from_scratch() {
+        let stone = BlockKind::Stone.default_state();
+        let mut columns = floor();
+        // a roof over a torch, then a hole in the floor
+        for x in 12..20 {
+            for z in 4..12 {
+                set_block(&mut columns, (x, 8, z), stone);
+            }
+        }
+        set_block(&mut columns, (14, 4, 6), BlockKind::Glowstone.default_state());
+        set_block(&mut columns, (17, 3, 7), BlockState::AIR);
+        set_block(&mut columns, (13, 8, 5), BlockState::AIR);
+        assert_eq!(light_at(&columns, LightKind::Sky, (16, 5, 8)), Some(11));
+        let fresh = relit(&columns);
+        for location in [(0, 0), (1, 0)].iter() {
+            assert!(columns[location].light() == fresh[location].light());
+        }
+    }
+
+    #[test]
+    fn columns_lit_alone_match_once_their_borders_are() {
+        let stone = BlockKind::Stone.default_state();
+        let mut columns = floor();
+        // a torch under a roof across the border and a shaft down the floor
+        for x in 10..22 {
+            for z in 4..12 {
+                set_block(&mut columns, (x, 8, z), stone);
+            }
+        }
+        set_block(&mut columns, (15, 4, 6), BlockKind::Torch.default_state());
+        set_block(&mut columns, (17, 3, 12), BlockState::AIR);
+        let fresh = relit(&columns);
+
+        let mut joined = HashMap::new();
+        for (location, mut column) in unlit(&columns).into_iter() {
+            light_column(&mut column, location);
+            assert!(column.is_lit());
+            joined.insert(location, column);
+            light_borders(&mut joined, location);
+        }
+        assert_eq!(light_at(&joined, LightKind::Block, (17, 5, 6)), Some(11));
+        assert_eq!(light_at(&joined, LightKind::Sky, (17, 3, 12)), Some(15));
+        for location in [(0, 0), (1, 0)].iter() {
+            assert!(joined[location].light() == fresh[location].light());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+pub mod serverbound {
+    use crate::packets;
+
+    packets! {
+        serverbound ServerboundLogin in Login {
+            0x00 => LoginStart { name: String },
+            0x01 => EncryptionResponse {
+                shared_secret: Vec<u8>,
+                verify_token: Vec<u8>,
+            },
+        }
+    }
+}
+
+pub mod clientbound {
+    use crate::packet::types::VarInt;
+    use crate::packets;
+
+    packets! {
+        clientbound ClientboundLogin in Login {
+            /// `reason` is a JSON chat component.
+            0x00 => Disconnect { reason: String },
+            0x01 => EncryptionRequest {
+                server_id: String,
+                public_key: Vec<u8>,
+                verify_token: Vec<u8>,
+            },
+            0x02 => LoginSuccess { uuid: String, username: String },
+            0x03 => SetCompression { threshold: VarInt },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clientbound::{LoginSuccess, SetCompression};
+    use super::serverbound::{EncryptionResponse, LoginStart, ServerboundLogin};
+    use crate::packet::reader::{McBytesErr, McBytesReader};
+    use crate::packet::types::{Clientbound, VarInt};
+    use crate::packet::version::ProtocolVersion;
+
+    #[test]
+    fn fields_are_encoded_in_order() {
+        let login_success = LoginSuccess {
+            uuid: String::from("069a79f4-44e9-4726-a5be-fca90e38aaf5"),
+            username: String::from("Notch"),
+        };
+        let data = login_success.to_builder(ProtocolVersion::LATEST).into_inner();
+        assert_eq!(data[..2], [0x02, 36]);
+        assert_eq!(data[2..38], *b"069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(data[38..], *b"\x05Notch");
+
+        let set_compression = SetCompression { threshold: VarInt(256) };
+        assert_eq!(set_compression.to_builder(ProtocolVersion::LATEST).into_inner(), [0x03, 0x80, 0x02]);
+    }
+
+    #[test]
+    fn packets_are_decoded_by_id() {
+        let mut reader = McBytesReader::from_vec(b"\x00\x05Notch".to_vec());
+        let packet = ServerboundLogin::decode(&mut reader, ProtocolVersion::LATEST).unwrap();
+        assert_eq!(packet, ServerboundLogin::LoginStart(LoginStart { name: String::from("Notch") }));
+
+        let mut reader = McBytesReader::from_vec(vec![0x01, 2, 7, 8, 1, 9]);
+        let packet = ServerboundLogin::decode(&mut reader, ProtocolVersion::V1_14_4).unwrap();
+        let encryption_response = EncryptionResponse {
+            shared_secret: vec![7, 8],
+            verify_token: vec![9],
+        };
+        assert_eq!(packet, ServerboundLogin::EncryptionResponse(encryption_response));
+        assert_eq!(reader.remaining(), 0);
+
+        let mut reader = McBytesReader::from_vec(vec![0x02, 0]);
+        let packet = ServerboundLogin::decode(&mut reader, ProtocolVersion::LATEST);
+        assert!(matches!(packet, Err(McBytesErr::UnknownPacketId(0x02))));
+    }
+}
@@ -0,0 +1,152 @@
+/// Declares the packets of one direction of a protocol state.
+///
+/// Every packet becomes a struct with public fields, encoded or decoded
+/// field by field in the declared order, and a variant of the state's enum.
+/// Ids are the ones of `ProtocolVersion::LATEST`, see `version::Registry`.
+/// A clientbound field marked with `[since VERSION]` is only sent to
+/// clients speaking that version or newer.
+///
+/// ```ignore
+/// packets! {
+///     serverbound ServerboundStatus in Status {
+///         0x00 => Request {},
+///         0x01 => Ping { payload: i64 },
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! packets {
+    (@present $version:ident) => {
+        true
+    };
+    (@present $version:ident $since:ident) => {
+        $version >= $crate::packet::version::ProtocolVersion::$since
+    };
+    (
+        serverbound $enum:ident in $state:ident {
+            $(
+                $(#[$meta:meta])*
+                $id:literal => $name:ident {
+                    $($(#[$field_meta:meta])* $field:ident : $ty:ty),* $(,)?
+                }
+            ),* $(,)?
+        }
+    ) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $name {
+                $($(#[$field_meta])* pub $field: $ty),*
+            }
+
+            impl $crate::packet::types::McDecode for $name {
+                #[allow(unused_variables)]
+                fn decode(
+                    reader: &mut $crate::packet::reader::McBytesReader,
+                ) -> Result<Self, $crate::packet::reader::McBytesErr> {
+                    Ok(Self {
+                        $($field: $crate::packet::types::McDecode::decode(reader)?),*
+                    })
+                }
+            }
+        )*
+
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $enum {
+            $($name($name)),*
+        }
+
+        impl $enum {
+            /// Reads the packet id and the packet it identifies in `version`.
+            pub fn decode(
+                reader: &mut $crate::packet::reader::McBytesReader,
+                version: $crate::packet::version::ProtocolVersion,
+            ) -> Result<Self, $crate::packet::reader::McBytesErr> {
+                let id = reader.read_varint()?;
+                let id = version
+                    .registry()
+                    .serverbound_id($crate::packet::ProtocolState::$state, id);
+                match id {
+                    $($id => Ok($enum::$name($crate::packet::types::McDecode::decode(reader)?)),)*
+                    id => Err($crate::packet::reader::McBytesErr::UnknownPacketId(id)),
+                }
+            }
+        }
+    };
+    (
+        clientbound $enum:ident in $state:ident {
+            $(
+                $(#[$meta:meta])*
+                $id:literal => $name:ident {
+                    $($(#[$field_meta:meta])* $field:ident : $ty:ty $([since $since:ident])?),* $(,)?
+                }
+            ),* $(,)?
+        }
+    ) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $name {
+                $($(#[$field_meta])* pub $field: $ty),*
+            }
+
+            impl $crate::packet::types::Clientbound for $name {
+                fn id(&self) -> i32 {
+                    $id
+                }
+
+                fn state(&self) -> $crate::packet::ProtocolState {
+                    $crate::packet::ProtocolState::$state
+                }
+
+                #[allow(unused_variables)]
+                fn encode(
+                    &self,
+                    version: $crate::packet::version::ProtocolVersion,
+                    builder: &mut $crate::packet::builder::PacketBuilder,
+                ) {
+                    $(
+                        if $crate::packets!(@present version $($since)?) {
+                            $crate::packet::types::McEncode::encode(&self.$field, builder);
+                        }
+                    )*
+                }
+            }
+
+            impl From<$name> for $enum {
+                fn from(packet: $name) -> Self {
+                    $enum::$name(packet)
+                }
+            }
+        )*
+
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $enum {
+            $($name($name)),*
+        }
+
+        impl $crate::packet::types::Clientbound for $enum {
+            fn id(&self) -> i32 {
+                match self {
+                    $($enum::$name(packet) => $crate::packet::types::Clientbound::id(packet),)*
+                }
+            }
+
+            fn state(&self) -> $crate::packet::ProtocolState {
+                $crate::packet::ProtocolState::$state
+            }
+
+            fn encode(
+                &self,
+                version: $crate::packet::version::ProtocolVersion,
+                builder: &mut $crate::packet::builder::PacketBuilder,
+            ) {
+                match self {
+                    $($enum::$name(packet) => {
+                        $crate::packet::types::Clientbound::encode(packet, version, builder)
+                    })*
+                }
+            }
+        }
+    };
+}
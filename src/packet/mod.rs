@@ -1,4 +1,22 @@
+pub mod macros;
+pub mod types;
 pub mod reader;
 pub mod builder;
 pub mod codec;
+pub mod cipher;
 pub mod chunk;
+pub mod block_entity;
+pub mod light;
+pub mod handshaking;
+pub mod status;
+pub mod login;
+pub mod play;
+pub mod version;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProtocolState {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+}
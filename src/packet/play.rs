@@ -0,0 +1,205 @@
+pub mod serverbound {
+    use crate::packet::types::{Position, Slot, VarInt};
+    use crate::packets;
+
+    packets! {
+        serverbound ServerboundPlay in Play {
+            0x00 => TeleportConfirm { teleport_id: VarInt },
+            0x03 => ChatMessage { message: String },
+            0x05 => ClientSettings {
+                locale: String,
+                view_distance: i8,
+                chat_mode: VarInt,
+                chat_colors: bool,
+                displayed_skin_parts: u8,
+                main_hand: VarInt,
+            },
+            0x0f => KeepAlive { keep_alive_id: i64 },
+            0x11 => PlayerPosition {
+                x: f64,
+                y: f64,
+                z: f64,
+                on_ground: bool,
+            },
+            0x12 => PlayerPositionAndRotation {
+                x: f64,
+                y: f64,
+                z: f64,
+                yaw: f32,
+                pitch: f32,
+                on_ground: bool,
+            },
+            0x13 => PlayerRotation {
+                yaw: f32,
+                pitch: f32,
+                on_ground: bool,
+            },
+            0x14 => PlayerMovement { on_ground: bool },
+            0x1a => PlayerDigging {
+                status: VarInt,
+                location: Position,
+                face: i8,
+            },
+            0x23 => HeldItemChange { slot: i16 },
+            0x26 => CreativeInventoryAction {
+                slot: i16,
+                clicked_item: Option<Slot>,
+            },
+            0x2a => Animation { hand: VarInt },
+            0x2c => PlayerBlockPlacement {
+                hand: VarInt,
+                location: Position,
+                face: VarInt,
+                cursor_x: f32,
+                cursor_y: f32,
+                cursor_z: f32,
+                inside_block: bool,
+            },
+        }
+    }
+}
+
+pub mod clientbound {
+    use crate::packet::builder::PacketBuilder;
+    use crate::packet::types::{McEncode, Position, RawBytes, VarInt};
+    use crate::packets;
+
+    /// Light arrays of the sections set in a light mask, each prefixed with its length.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct LightArrays(pub Vec<Vec<u8>>);
+
+    impl McEncode for LightArrays {
+        fn encode(&self, builder: &mut PacketBuilder) {
+            for array in self.0.iter() {
+                array.encode(builder);
+            }
+        }
+    }
+
+    packets! {
+        clientbound ClientboundPlay in Play {
+            /// `action` tells which kind of block entity `nbt` describes.
+            0x0a => BlockEntityData {
+                location: Position,
+                action: u8,
+                /// NBT compound of the block entity.
+                nbt: RawBytes,
+            },
+            /// `slots` holds `count` encoded slots.
+            0x15 => WindowItems {
+                window_id: u8,
+                count: i16,
+                slots: RawBytes,
+            },
+            /// `reason` is a JSON chat component.
+            0x1b => Disconnect { reason: String },
+            /// Tells the client to forget a column it was sent.
+            0x1e => UnloadChunk { chunk_x: i32, chunk_z: i32 },
+            0x21 => KeepAlive { keep_alive_id: i64 },
+            0x22 => ChunkData {
+                chunk_x: i32,
+                chunk_z: i32,
+                full_chunk: bool,
+                primary_bit_mask: VarInt,
+                /// NBT compound of the heightmaps.
+                heightmaps: RawBytes,
+                /// Before 1.15 biomes are sent at the end of `data` instead.
+                biomes: Box<[i32; 1024]> [since V1_15],
+                data: Vec<u8>,
+                /// NBT compounds of the block entities.
+                block_entities: Vec<RawBytes>,
+            },
+            0x25 => UpdateLight {
+                chunk_x: VarInt,
+                chunk_z: VarInt,
+                sky_light_mask: VarInt,
+                block_light_mask: VarInt,
+                empty_sky_light_mask: VarInt,
+                empty_block_light_mask: VarInt,
+                sky_light: LightArrays,
+                block_light: LightArrays,
+            },
+            0x26 => JoinGame {
+                entity_id: i32,
+                gamemode: u8,
+                dimension: i32,
+                hashed_seed: i64 [since V1_15],
+                max_players: u8,
+                level_type: String,
+                view_distance: VarInt,
+                reduced_debug_info: bool,
+                enable_respawn_screen: bool [since V1_15],
+            },
+            0x36 => PlayerPositionAndLook {
+                x: f64,
+                y: f64,
+                z: f64,
+                yaw: f32,
+                pitch: f32,
+                flags: u8,
+                teleport_id: VarInt,
+            },
+            /// Column the player is in, clients drop the chunks farther than
+            /// their view distance from it.
+            0x41 => UpdateViewPosition { chunk_x: VarInt, chunk_z: VarInt },
+            0x4e => SpawnPosition { location: Position },
+            0x4f => TimeUpdate { world_age: i64, time_of_day: i64 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clientbound::{ClientboundPlay, KeepAlive, UpdateViewPosition};
+    use super::serverbound::{self, PlayerPosition, ServerboundPlay};
+    use crate::packet::builder::PacketBuilder;
+    use crate::packet::reader::{McBytesErr, McBytesReader};
+    use crate::packet::types::{Clientbound, VarInt};
+    use crate::packet::version::ProtocolVersion;
+
+    #[test]
+    fn fields_are_encoded_in_order() {
+        let keep_alive = KeepAlive { keep_alive_id: 0x0102_0304_0506_0708 };
+        let data = keep_alive.to_builder(ProtocolVersion::LATEST).into_inner();
+        assert_eq!(data, [0x21, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let packet = ClientboundPlay::from(keep_alive);
+        assert_eq!(packet.to_builder(ProtocolVersion::LATEST).into_inner(), data);
+
+        let update_view_position = UpdateViewPosition {
+            chunk_x: VarInt(-1),
+            chunk_z: VarInt(300),
+        };
+        let data = update_view_position.to_builder(ProtocolVersion::LATEST).into_inner();
+        assert_eq!(data, [0x41, 0xff, 0xff, 0xff, 0xff, 0x0f, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn packets_are_decoded_by_id() {
+        let mut builder = PacketBuilder::new();
+        builder.push_varint(0x11);
+        builder.push_double(0.5);
+        builder.push_double(64.0);
+        builder.push_double(-3.25);
+        builder.push_bool(true);
+        builder.push_varint(0x0f);
+        builder.push_long(42);
+        let mut reader = McBytesReader::from_vec(builder.into_inner());
+
+        let player_position = PlayerPosition {
+            x: 0.5,
+            y: 64.0,
+            z: -3.25,
+            on_ground: true,
+        };
+        let packet = ServerboundPlay::decode(&mut reader, ProtocolVersion::LATEST).unwrap();
+        assert_eq!(packet, ServerboundPlay::PlayerPosition(player_position));
+        let packet = ServerboundPlay::decode(&mut reader, ProtocolVersion::LATEST).unwrap();
+        let keep_alive = serverbound::KeepAlive { keep_alive_id: 42 };
+        assert_eq!(packet, ServerboundPlay::KeepAlive(keep_alive));
+        assert_eq!(reader.remaining(), 0);
+
+        let mut reader = McBytesReader::from_vec(vec![0x7f]);
+        let packet = ServerboundPlay::decode(&mut reader, ProtocolVersion::LATEST);
+        assert!(matches!(packet, Err(McBytesErr::UnknownPacketId(0x7f))));
+    }
+}
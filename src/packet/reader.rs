@@ -1,7 +1,10 @@
 use std::vec::IntoIter;
-use std::convert::TryInto;
 
 use thiserror::Error;
+use uuid::Uuid;
+
+use crate::nbt::{self, Tag};
+use crate::packet::types::{Position, Slot};
 
 #[derive(Debug, Clone, Copy, Error)]
 pub enum McBytesErr {
@@ -9,33 +12,69 @@ pub enum McBytesErr {
     VarintTooBig,
     #[error("Insufficient data to read.")]
     InsufficientBytes,
+    #[error("Unknown packet id: {0:#x}")]
+    UnknownPacketId(i32),
+    #[error("Invalid NBT tag type: {0}")]
+    InvalidNbtTag(u8),
+    #[error("NBT is nested too deeply.")]
+    NbtTooDeep,
+    #[error("Negative length: {0}")]
+    NegativeLength(i32),
+    #[error("String of {0} bytes is over the maximum length.")]
+    StringTooLong(usize),
 }
 
+/// Maximum length of a string in characters, unless a packet says otherwise.
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 32767;
+
 /// Minecraft bytes
 #[derive(Debug)]
 pub struct McBytesReader {
     iter: IntoIter<u8>,
+    max_string_length: usize,
 }
 
 impl McBytesReader {
     pub fn from_vec(data: Vec<u8>) -> Self {
         Self {
             iter: data.into_iter(),
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
         }
     }
 
+    /// Rejects strings longer than `length` characters.
+    pub fn with_max_string_length(mut self, length: usize) -> Self {
+        self.max_string_length = length;
+        self
+    }
+
     pub fn read_varint(&mut self) -> Result<i32, McBytesErr> {
-        let mut num_read: i32 = 0;
+        let mut num_read = 0;
         let mut result: i32 = 0;
-        let mut buf: u8;
         loop {
-            buf = self.iter.next().unwrap();
-            let value = buf & 0b01111111;
-            result |= (value as i32) << (7 * num_read);
+            if num_read == 5 {
+                return Err(McBytesErr::VarintTooBig);
+            }
+            let buf = self.read_one_byte()?;
+            result |= ((buf & 0b01111111) as i32) << (7 * num_read);
             num_read += 1;
-            if num_read > 5 {
+            if (buf & 0b10000000) == 0 {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn read_varlong(&mut self) -> Result<i64, McBytesErr> {
+        let mut num_read = 0;
+        let mut result: i64 = 0;
+        loop {
+            if num_read == 10 {
                 return Err(McBytesErr::VarintTooBig);
             }
+            let buf = self.read_one_byte()?;
+            result |= ((buf & 0b01111111) as i64) << (7 * num_read);
+            num_read += 1;
             if (buf & 0b10000000) == 0 {
                 break;
             }
@@ -43,33 +82,259 @@ impl McBytesReader {
         Ok(result)
     }
 
+    /// Reads a VarInt length prefix, which may not be negative.
+    pub fn read_length(&mut self) -> Result<usize, McBytesErr> {
+        let length = self.read_varint()?;
+        if length < 0 {
+            return Err(McBytesErr::NegativeLength(length));
+        }
+        Ok(length as usize)
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.iter.len()
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, McBytesErr> {
+        Ok(self.read_one_byte()? != 0)
+    }
+
+    pub fn read_byte(&mut self) -> Result<i8, McBytesErr> {
+        Ok(self.read_one_byte()? as i8)
+    }
+
+    pub fn read_unsigned_byte(&mut self) -> Result<u8, McBytesErr> {
+        self.read_one_byte()
+    }
+
+    pub fn read_short(&mut self) -> Result<i16, McBytesErr> {
+        Ok(i16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_int(&mut self) -> Result<i32, McBytesErr> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_float(&mut self) -> Result<f32, McBytesErr> {
+        Ok(f32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a position packed as x (26 bits), z (26 bits) and y (12 bits),
+    /// the layout written by `PacketBuilder::push_position`.
+    pub fn read_position(&mut self) -> Result<Position, McBytesErr> {
+        let value = self.read_long()?;
+        Ok(Position {
+            x: (value >> 38) as i32,
+            y: (value << 52 >> 52) as i32,
+            z: (value << 26 >> 38) as i32,
+        })
+    }
+
+    pub fn read_uuid(&mut self) -> Result<Uuid, McBytesErr> {
+        Ok(Uuid::from_bytes(self.read_array()?))
+    }
+
+    /// Reads a rotation in steps of 1/256 of a full turn.
+    pub fn read_angle(&mut self) -> Result<u8, McBytesErr> {
+        self.read_one_byte()
+    }
+
+    /// Reads a namespaced identifier such as `minecraft:stone`.
+    pub fn read_identifier(&mut self) -> Result<String, McBytesErr> {
+        self.read_string()
+    }
+
+    /// Reads a byte array prefixed with its length as a VarInt.
+    pub fn read_byte_array(&mut self) -> Result<Vec<u8>, McBytesErr> {
+        let length = self.read_length()?;
+        self.read_bytes(length)
+    }
+
+    /// Reads a named NBT tag, `None` for a lone TAG_End. Packets always
+    /// leave the name empty.
+    pub fn read_nbt(&mut self) -> Result<Option<Tag>, McBytesErr> {
+        Ok(nbt::read_tag(self)?.map(|(_, tag)| tag))
+    }
+
+    pub fn read_slot(&mut self) -> Result<Option<Slot>, McBytesErr> {
+        if !self.read_bool()? {
+            return Ok(None);
+        }
+        Ok(Some(Slot {
+            item_id: self.read_varint()?,
+            count: self.read_byte()?,
+            nbt: self.read_nbt()?,
+        }))
+    }
+
     pub fn read_unsigned_short(&mut self) -> Result<u16, McBytesErr> {
-        let result: u16 = self.read_one_byte()? as u16 + ((self.read_one_byte()? as u16) << 8);
-        Ok(result)
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_long(&mut self) -> Result<i64, McBytesErr> {
+        Ok(i64::from_be_bytes(self.read_array()?))
     }
 
     pub fn read_double(&mut self) -> Result<f64, McBytesErr> {
-        let buf = self.read_bytes(8)?;
-        let array: [u8; 8] = buf.as_slice().try_into().unwrap();
-        Ok(f64::from_be_bytes(array))
+        Ok(f64::from_be_bytes(self.read_array()?))
     }
 
     pub fn read_string(&mut self) -> Result<String, McBytesErr> {
-        let length = self.read_varint()?;
+        let length = self.read_length()?;
+        // a character takes up to 4 bytes in UTF-8
+        if length > self.max_string_length.saturating_mul(4) {
+            return Err(McBytesErr::StringTooLong(length));
+        }
         let result = self.read_bytes(length)?;
-        Ok(String::from_utf8_lossy(result.as_slice()).into_owned())
+        let result = String::from_utf8_lossy(result.as_slice()).into_owned();
+        if result.chars().count() > self.max_string_length {
+            return Err(McBytesErr::StringTooLong(length));
+        }
+        Ok(result)
     }
 
     pub fn read_one_byte(&mut self) -> Result<u8, McBytesErr> {
         self.iter.next().ok_or(McBytesErr::InsufficientBytes)
     }
 
-    pub fn read_bytes(&mut self, n: i32) -> Result<Vec<u8>, McBytesErr> {
-        let mut result = Vec::new();
-        for _ in 0..n {
-            result.push(self.read_one_byte()?);
+    pub fn read_bytes(&mut self, n: usize) -> Result<Vec<u8>, McBytesErr> {
+        if n > self.remaining() {
+            return Err(McBytesErr::InsufficientBytes);
+        }
+        Ok(self.iter.by_ref().take(n).collect())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], McBytesErr> {
+        let mut result = [0; N];
+        for byte in result.iter_mut() {
+            *byte = self.read_one_byte()?;
         }
         Ok(result)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::builder::PacketBuilder;
+    use crate::packet::types::McEncode;
+
+    fn reader(builder: PacketBuilder) -> McBytesReader {
+        McBytesReader::from_vec(builder.into_inner())
+    }
+
+    #[test]
+    fn position_round_trip() {
+        let mut builder = PacketBuilder::new();
+        builder.push_position(-33554432, -2048, 33554431);
+        builder.push_position(18357644, 831, -20882616);
+        let mut reader = reader(builder);
+        assert_eq!(reader.read_position().unwrap(), Position { x: -33554432, y: -2048, z: 33554431 });
+        assert_eq!(reader.read_position().unwrap(), Position { x: 18357644, y: 831, z: -20882616 });
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let mut builder = PacketBuilder::new();
+        for n in [0, 1, 300, -1, -2, i32::MIN, i32::MAX].iter() {
+            builder.push_varint(*n);
+        }
+        let data = builder.into_inner();
+        assert_eq!(data[4..9], [0xff, 0xff, 0xff, 0xff, 0x0f]);
+        let mut reader = McBytesReader::from_vec(data);
+        for n in [0, 1, 300, -1, -2, i32::MIN, i32::MAX].iter() {
+            assert_eq!(reader.read_varint().unwrap(), *n);
+        }
+    }
+
+    #[test]
+    fn varlong_round_trip() {
+        let mut builder = PacketBuilder::new();
+        for n in [0, 1, 2147483648, -1, i64::MIN, i64::MAX].iter() {
+            builder.push_varlong(*n);
+        }
+        let mut reader = reader(builder);
+        for n in [0, 1, 2147483648, -1, i64::MIN, i64::MAX].iter() {
+            assert_eq!(reader.read_varlong().unwrap(), *n);
+        }
+    }
+
+    #[test]
+    fn slot_with_nbt() {
+        // {display:{Name:"x"}, list:[I;1,2]}
+        let bytes = vec![
+            10, 0, 0,
+            10, 0, 7, b'd', b'i', b's', b'p', b'l', b'a', b'y',
+            8, 0, 4, b'N', b'a', b'm', b'e', 0, 1, b'x',
+            0,
+            11, 0, 4, b'l', b'i', b's', b't', 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2,
+            0,
+        ];
+        let slot = Slot {
+            item_id: 1,
+            count: 64,
+            nbt: Some(nbt::snbt::parse(r#"{display:{Name:"x"},list:[I;1,2]}"#).unwrap()),
+        };
+        let mut builder = PacketBuilder::new();
+        slot.encode(&mut builder);
+        assert_eq!(builder.into_inner()[2..], bytes[..]);
+        let mut builder = PacketBuilder::new();
+        Some(slot.clone()).encode(&mut builder);
+        None::<Slot>.encode(&mut builder);
+        builder.push_byte(0xFF);
+        let mut reader = reader(builder);
+        assert_eq!(reader.read_slot().unwrap(), Some(slot));
+        assert_eq!(reader.read_slot().unwrap(), None);
+        assert_eq!(reader.read_unsigned_byte().unwrap(), 0xFF);
+    }
+
+    #[test]
+    fn short_input_is_an_error() {
+        let mut reader = McBytesReader::from_vec(vec![0x12, 0x34, 0x56]);
+        assert!(matches!(reader.read_int(), Err(McBytesErr::InsufficientBytes)));
+        let mut reader = McBytesReader::from_vec(vec![1, 1]);
+        assert!(matches!(reader.read_slot(), Err(McBytesErr::InsufficientBytes)));
+        let mut reader = McBytesReader::from_vec(vec![13, 0, 0]);
+        assert!(matches!(reader.read_nbt(), Err(McBytesErr::InvalidNbtTag(13))));
+    }
+
+    #[test]
+    fn malicious_lengths_are_errors() {
+        let mut reader = McBytesReader::from_vec(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x01]);
+        assert!(matches!(reader.read_varint(), Err(McBytesErr::VarintTooBig)));
+        let mut reader = McBytesReader::from_vec(vec![0x80]);
+        assert!(matches!(reader.read_varint(), Err(McBytesErr::InsufficientBytes)));
+        let mut reader = McBytesReader::from_vec(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+        assert!(matches!(reader.read_string(), Err(McBytesErr::NegativeLength(-1))));
+        let mut reader = McBytesReader::from_vec(vec![0xFF, 0xFF, 0xFF, 0xFF, 0x07]);
+        assert!(matches!(reader.read_byte_array(), Err(McBytesErr::InsufficientBytes)));
+
+        let mut builder = PacketBuilder::new();
+        builder.push_string("abcdef");
+        let mut short = McBytesReader::from_vec(builder.into_inner()).with_max_string_length(5);
+        assert!(matches!(short.read_string(), Err(McBytesErr::StringTooLong(6))));
+        let mut builder = PacketBuilder::new();
+        builder.push_string("abcdef");
+        let mut long = McBytesReader::from_vec(builder.into_inner()).with_max_string_length(usize::MAX);
+        assert_eq!(long.read_string().unwrap(), "abcdef");
+    }
+
+    #[test]
+    fn unsigned_short_is_big_endian() {
+        let mut reader = McBytesReader::from_vec(vec![0x1E, 0x61]);
+        assert_eq!(reader.read_unsigned_short().unwrap(), 7777);
+    }
+
+    #[test]
+    fn deeply_nested_nbt_is_an_error() {
+        // a list of lists of lists...
+        let mut nbt = vec![9, 0, 0];
+        for _ in 0..1000 {
+            nbt.extend(&[9, 0, 0, 0, 1]);
+        }
+        let mut reader = McBytesReader::from_vec(nbt);
+        assert!(matches!(reader.read_nbt(), Err(McBytesErr::NbtTooDeep)));
+    }
+}
+
@@ -0,0 +1,145 @@
+use crate::packet::builder::PacketBuilder;
+
+use serde::Serialize;
+
+/// Most players listed in the sample of a status response, as in vanilla.
+pub const MAX_SAMPLE: usize = 12;
+
+// see https://wiki.vg/Server_List_Ping#Response
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusResponse {
+    pub version: StatusVersion,
+    pub players: StatusPlayers,
+    pub description: StatusDescription,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusPlayers {
+    pub max: u32,
+    pub online: u32,
+    pub sample: Vec<PlayerSample>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusDescription {
+    pub text: String,
+}
+
+impl StatusResponse {
+    /// Counts every online player in `players` but only lists the first
+    /// `MAX_SAMPLE` of them.
+    pub fn new(name: &str, protocol: i32, max: u32, mut players: Vec<PlayerSample>, motd: &str) -> Self {
+        let online = players.len() as u32;
+        players.truncate(MAX_SAMPLE);
+        Self {
+            version: StatusVersion {
+                name: String::from(name),
+                protocol,
+            },
+            players: StatusPlayers {
+                max,
+                online,
+                sample: players,
+            },
+            description: StatusDescription {
+                text: String::from(motd),
+            },
+        }
+    }
+
+    pub fn packet(&self) -> clientbound::Response {
+        clientbound::Response {
+            json: serde_json::to_string(self).unwrap(),
+        }
+    }
+
+    /// Builds the kick packet answering a legacy server list ping.
+    // see https://wiki.vg/Server_List_Ping#1.6
+    pub fn build_legacy(&self) -> Vec<u8> {
+        let text = format!(
+            "\u{a7}1\0{}\0{}\0{}\0{}\0{}",
+            self.version.protocol,
+            self.version.name,
+            self.description.text,
+            self.players.online,
+            self.players.max,
+        );
+        let chars: Vec<u16> = text.encode_utf16().collect();
+        let mut builder = PacketBuilder::new();
+        builder.push_byte(0xFF);
+        builder.push_unsigned_char(chars.len() as u16);
+        for c in chars {
+            builder.push_unsigned_char(c);
+        }
+        builder.into_inner()
+    }
+}
+
+pub mod serverbound {
+    use crate::packets;
+
+    packets! {
+        serverbound ServerboundStatus in Status {
+            0x00 => Request {},
+            0x01 => Ping { payload: i64 },
+        }
+    }
+}
+
+pub mod clientbound {
+    use crate::packets;
+
+    packets! {
+        clientbound ClientboundStatus in Status {
+            0x00 => Response { json: String },
+            0x01 => Pong { payload: i64 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_response_is_utf16() {
+        let status = StatusResponse::new("1.15.2", 578, 20, Vec::new(), "Hi");
+        let mut expected = vec![0xFF, 0, 21, 0x00, 0xA7];
+        for byte in b"1\x00578\x001.15.2\x00Hi\x000\x0020".iter() {
+            expected.extend(&[0, *byte]);
+        }
+        assert_eq!(status.build_legacy(), expected);
+    }
+
+    #[test]
+    fn samples_list_at_most_12_players() {
+        let players = (0..20)
+            .map(|i| PlayerSample {
+                name: format!("player{}", i),
+                id: format!("00000000-0000-0000-0000-{:012}", i),
+            })
+            .collect();
+        let status = StatusResponse::new("1.15.2", 578, 100, players, "Hi");
+        let json: serde_json::Value = serde_json::from_str(&status.packet().json).unwrap();
+        assert_eq!(json["version"], serde_json::json!({ "name": "1.15.2", "protocol": 578 }));
+        assert_eq!(json["players"]["max"], 100);
+        assert_eq!(json["players"]["online"], 20);
+        let sample = json["players"]["sample"].as_array().unwrap();
+        assert_eq!(sample.len(), MAX_SAMPLE);
+        let first = serde_json::json!({ "name": "player0", "id": "00000000-0000-0000-0000-000000000000" });
+        assert_eq!(sample[0], first);
+        assert_eq!(json["description"], serde_json::json!({ "text": "Hi" }));
+    }
+}
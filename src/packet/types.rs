@@ -0,0 +1,257 @@
+use crate::nbt::{self, Tag};
+use crate::packet::builder::PacketBuilder;
+use crate::packet::reader::{McBytesErr, McBytesReader};
+use crate::packet::version::ProtocolVersion;
+use crate::packet::ProtocolState;
+
+use uuid::Uuid;
+
+/// A value which can be written as part of a packet.
+pub trait McEncode {
+    fn encode(&self, builder: &mut PacketBuilder);
+}
+
+/// A value which can be read as part of a packet.
+pub trait McDecode: Sized {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr>;
+}
+
+/// A packet sent by the server.
+pub trait Clientbound {
+    /// Returns the packet id in `ProtocolVersion::LATEST`.
+    fn id(&self) -> i32;
+
+    fn state(&self) -> ProtocolState;
+
+    /// Writes the fields in the layout used by `version`.
+    fn encode(&self, version: ProtocolVersion, builder: &mut PacketBuilder);
+
+    /// Returns a builder holding the packet id and fields, ready to be built into a frame.
+    fn to_builder(&self, version: ProtocolVersion) -> PacketBuilder {
+        let mut builder = PacketBuilder::new();
+        builder.push_varint(version.registry().clientbound_id(self.state(), self.id()));
+        self.encode(version, &mut builder);
+        builder
+    }
+}
+
+/// An `i32` sent as a VarInt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarInt(pub i32);
+
+/// An `i64` sent as a VarLong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarLong(pub i64);
+
+/// A rotation in steps of 1/256 of a full turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Angle(pub u8);
+
+/// A namespaced identifier such as `minecraft:stone`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Identifier(pub String);
+
+/// The contents of a non-empty inventory slot.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Slot {
+    pub item_id: i32,
+    pub count: i8,
+    /// The NBT compound of the item.
+    pub nbt: Option<Tag>,
+}
+
+/// Block coordinates packed into a single long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Bytes written as they are, without a length prefix.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RawBytes(pub Vec<u8>);
+
+macro_rules! impl_be_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl McEncode for $ty {
+                fn encode(&self, builder: &mut PacketBuilder) {
+                    builder.push_vec_u8(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_be_bytes!(u8, i8, i16, u16, i32, i64, f32, f64);
+
+impl McEncode for bool {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_bool(*self);
+    }
+}
+
+impl McEncode for String {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_string(self);
+    }
+}
+
+impl McEncode for VarInt {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_varint(self.0);
+    }
+}
+
+impl McEncode for VarLong {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_varlong(self.0);
+    }
+}
+
+impl McEncode for Angle {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_byte(self.0);
+    }
+}
+
+impl McEncode for Identifier {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_string(&self.0);
+    }
+}
+
+impl McEncode for Uuid {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_vec_u8(self.as_bytes());
+    }
+}
+
+impl McEncode for Slot {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_varint(self.item_id);
+        builder.push_byte(self.count as u8);
+        // tags read from packets always fit, only those built with strings
+        // too long for NBT are left out
+        let mut nbt = PacketBuilder::new();
+        match &self.nbt {
+            Some(tag) if nbt::write_tag(&mut nbt, "", tag).is_ok() => builder.push_vec_u8(&nbt.into_inner()),
+            _ => builder.push_byte(0),
+        }
+    }
+}
+
+impl McEncode for Position {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_position(self.x as i64, self.y as i64, self.z as i64);
+    }
+}
+
+impl McEncode for RawBytes {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_vec_u8(&self.0);
+    }
+}
+
+/// Optional values are prefixed with a boolean telling whether they are present.
+impl<T: McEncode> McEncode for Option<T> {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_bool(self.is_some());
+        if let Some(value) = self {
+            value.encode(builder);
+        }
+    }
+}
+
+/// Arrays are prefixed with their length as a VarInt.
+impl<T: McEncode> McEncode for Vec<T> {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        builder.push_varint(self.len() as i32);
+        for item in self.iter() {
+            item.encode(builder);
+        }
+    }
+}
+
+/// Fixed size arrays have no length prefix.
+impl<T: McEncode, const N: usize> McEncode for [T; N] {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        for item in self.iter() {
+            item.encode(builder);
+        }
+    }
+}
+
+impl<T: McEncode> McEncode for Box<T> {
+    fn encode(&self, builder: &mut PacketBuilder) {
+        self.as_ref().encode(builder);
+    }
+}
+
+macro_rules! impl_decode {
+    ($($ty:ty => $read:ident),* $(,)?) => {
+        $(
+            impl McDecode for $ty {
+                fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+                    reader.$read()
+                }
+            }
+        )*
+    };
+}
+
+impl_decode! {
+    bool => read_bool,
+    u8 => read_unsigned_byte,
+    i8 => read_byte,
+    u16 => read_unsigned_short,
+    i16 => read_short,
+    i32 => read_int,
+    i64 => read_long,
+    f32 => read_float,
+    f64 => read_double,
+    String => read_string,
+    Position => read_position,
+    Uuid => read_uuid,
+    Option<Slot> => read_slot,
+}
+
+impl McDecode for VarInt {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+        Ok(VarInt(reader.read_varint()?))
+    }
+}
+
+impl McDecode for VarLong {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+        Ok(VarLong(reader.read_varlong()?))
+    }
+}
+
+impl McDecode for Angle {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+        Ok(Angle(reader.read_angle()?))
+    }
+}
+
+impl McDecode for Identifier {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+        Ok(Identifier(reader.read_identifier()?))
+    }
+}
+
+impl<T: McDecode> McDecode for Vec<T> {
+    fn decode(reader: &mut McBytesReader) -> Result<Self, McBytesErr> {
+        let length = reader.read_length()?;
+        // every element takes at least a byte, so a longer count is a lie
+        if length > reader.remaining() {
+            return Err(McBytesErr::InsufficientBytes);
+        }
+        let mut result = Vec::with_capacity(length);
+        for _ in 0..length {
+            result.push(T::decode(reader)?);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::packet::ProtocolState;
+
+/// Protocol versions the server can speak, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    V1_14_4,
+    V1_15,
+    V1_15_1,
+    V1_15_2,
+}
+
+impl ProtocolVersion {
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V1_15_2;
+
+    pub const ALL: [ProtocolVersion; 4] = [
+        ProtocolVersion::V1_14_4,
+        ProtocolVersion::V1_15,
+        ProtocolVersion::V1_15_1,
+        ProtocolVersion::V1_15_2,
+    ];
+
+    /// Returns the version matching the protocol number sent in Handshake.
+    pub fn from_protocol(protocol: i32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|v| v.protocol() == protocol)
+    }
+
+    pub fn protocol(self) -> i32 {
+        match self {
+            ProtocolVersion::V1_14_4 => 498,
+            ProtocolVersion::V1_15 => 573,
+            ProtocolVersion::V1_15_1 => 575,
+            ProtocolVersion::V1_15_2 => 578,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProtocolVersion::V1_14_4 => "1.14.4",
+            ProtocolVersion::V1_15 => "1.15",
+            ProtocolVersion::V1_15_1 => "1.15.1",
+            ProtocolVersion::V1_15_2 => "1.15.2",
+        }
+    }
+
+    pub fn registry(self) -> &'static Registry {
+        match self {
+            ProtocolVersion::V1_14_4 => &V1_14_4,
+            _ => &V1_15,
+        }
+    }
+
+    /// Returns a human readable list of the supported releases.
+    pub fn supported_names() -> String {
+        let names: Vec<&str> = Self::ALL.iter().map(|v| v.name()).collect();
+        names.join(", ")
+    }
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self::LATEST
+    }
+}
+
+/// Packet ids of one protocol version.
+///
+/// Packets are declared with their ids in `ProtocolVersion::LATEST`, the
+/// tables map those to the ids of this version for every packet that moved.
+#[derive(Debug)]
+pub struct Registry {
+    /// `(state, latest id, id in this version)`
+    clientbound: &'static [(ProtocolState, i32, i32)],
+    /// `(state, latest id, id in this version)`
+    serverbound: &'static [(ProtocolState, i32, i32)],
+}
+
+impl Registry {
+    /// Returns the id to send a clientbound packet with.
+    pub fn clientbound_id(&self, state: ProtocolState, id: i32) -> i32 {
+        self.clientbound
+            .iter()
+            .find(|(s, latest, _)| *s == state && *latest == id)
+            .map_or(id, |(_, _, own)| *own)
+    }
+
+    /// Returns the latest id of a received serverbound packet.
+    pub fn serverbound_id(&self, state: ProtocolState, id: i32) -> i32 {
+        self.serverbound
+            .iter()
+            .find(|(s, _, own)| *s == state && *own == id)
+            .map_or(id, |(_, latest, _)| *latest)
+    }
+}
+
+static V1_15: Registry = Registry {
+    clientbound: &[],
+    serverbound: &[],
+};
+
+// see https://wiki.vg/index.php?title=Protocol&oldid=15346
+static V1_14_4: Registry = Registry {
+    clientbound: &[
+        (ProtocolState::Play, 0x0a, 0x09), // Block Entity Data
+        (ProtocolState::Play, 0x15, 0x14), // Window Items
+        (ProtocolState::Play, 0x1b, 0x1a), // Disconnect
+        (ProtocolState::Play, 0x1e, 0x1d), // Unload Chunk
+        (ProtocolState::Play, 0x21, 0x20), // Keep Alive
+        (ProtocolState::Play, 0x22, 0x21), // Chunk Data
+        (ProtocolState::Play, 0x25, 0x24), // Update Light
+        (ProtocolState::Play, 0x26, 0x25), // Join Game
+        (ProtocolState::Play, 0x36, 0x35), // Player Position And Look
+        (ProtocolState::Play, 0x41, 0x40), // Update View Position
+        (ProtocolState::Play, 0x4e, 0x4d), // Spawn Position
+        (ProtocolState::Play, 0x4f, 0x4e), // Time Update
+    ],
+    serverbound: &[],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::play::clientbound::JoinGame;
+    use crate::packet::types::{Clientbound, VarInt};
+
+    #[test]
+    fn versions_are_found_by_protocol() {
+        assert_eq!(ProtocolVersion::from_protocol(498), Some(ProtocolVersion::V1_14_4));
+        assert_eq!(ProtocolVersion::from_protocol(578), Some(ProtocolVersion::V1_15_2));
+        assert_eq!(ProtocolVersion::from_protocol(999), None);
+        assert_eq!(ProtocolVersion::supported_names(), "1.14.4, 1.15, 1.15.1, 1.15.2");
+    }
+
+    #[test]
+    fn moved_packets_are_remapped() {
+        let registry = ProtocolVersion::V1_14_4.registry();
+        assert_eq!(registry.clientbound_id(ProtocolState::Play, 0x22), 0x21);
+        // Login packets kept their ids
+        assert_eq!(registry.clientbound_id(ProtocolState::Login, 0x02), 0x02);
+        assert_eq!(registry.serverbound_id(ProtocolState::Play, 0x11), 0x11);
+        let latest = ProtocolVersion::LATEST.registry();
+        assert_eq!(latest.clientbound_id(ProtocolState::Play, 0x22), 0x22);
+    }
+
+    #[test]
+    fn fields_are_left_out_for_older_versions() {
+        let join_game = JoinGame {
+            entity_id: 1,
+            gamemode: 1,
+            dimension: 0,
+            hashed_seed: -1,
+            max_players: 20,
+            level_type: String::from("flat"),
+            view_distance: VarInt(10),
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+        };
+        let latest = join_game.to_builder(ProtocolVersion::LATEST).into_inner();
+        let old = join_game.to_builder(ProtocolVersion::V1_14_4).into_inner();
+        let (head, tail) = ([0, 0, 0, 1, 1, 0, 0, 0, 0], [20, 4, b'f', b'l', b'a', b't', 10, 0]);
+        assert_eq!(latest[0], 0x26);
+        assert_eq!(latest[1..10], head);
+        assert_eq!(latest[10..18], [0xff; 8]);
+        assert_eq!(latest[18..26], tail);
+        assert_eq!(latest[26..], [1]);
+        assert_eq!(old[0], 0x25);
+        assert_eq!(old[1..10], head);
+        assert_eq!(old[10..], tail);
+    }
+}
@@ -0,0 +1,441 @@
+//! Chunk columns loaded by the server, shared by every player looking at
+//! them and encoded once for each protocol version.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::anvil::{AnvilErr, RegionStorage};
+use crate::block::BlockState;
+use crate::generator::WorldGenerator;
+use crate::packet::chunk::{ChunkColumn, ChunkPacket};
+use crate::packet::light::{self, Columns};
+use crate::packet::types::Clientbound;
+use crate::packet::version::ProtocolVersion;
+
+/// Update Light and Chunk Data of a column, encoded for one protocol version.
+#[derive(Debug, PartialEq)]
+pub struct EncodedChunk {
+    pub update_light: Vec<u8>,
+    pub chunk_data: Vec<u8>,
+}
+
+impl EncodedChunk {
+    fn new(column: &ChunkColumn, version: ProtocolVersion) -> Self {
+        let update_light = column.light().packet(column.location());
+        Self {
+            update_light: update_light.to_builder(version).into_inner(),
+            chunk_data: ChunkPacket::new(column).packet(version).to_builder(version).into_inner(),
+        }
+    }
+}
+
+struct LoadedColumn {
+    column: ChunkColumn,
+    /// Players the column was sent to, it may be unloaded once there are none.
+    viewers: AtomicUsize,
+    /// Packets of the column by protocol version, cleared when it changes.
+    encoded: Mutex<HashMap<ProtocolVersion, Arc<EncodedChunk>>>,
+    /// Whether the column was generated in place of a saved chunk which
+    /// could not be read, it is never saved so that the chunk is kept.
+    unsaved: bool,
+}
+
+impl LoadedColumn {
+    fn new(column: ChunkColumn, unsaved: bool) -> Self {
+        Self {
+            column,
+            viewers: AtomicUsize::new(0),
+            encoded: Mutex::new(HashMap::new()),
+            unsaved,
+        }
+    }
+
+    fn needs_saving(&self) -> bool {
+        self.column.is_dirty() && !self.unsaved
+    }
+
+    fn invalidate(&mut self) {
+        self.encoded.get_mut().unwrap().clear();
+    }
+}
+
+type LoadedColumns = HashMap<(i32, i32), LoadedColumn>;
+
+impl Columns for LoadedColumns {
+    fn column(&self, location: (i32, i32)) -> Option<&ChunkColumn> {
+        self.get(&location).map(|loaded| &loaded.column)
+    }
+
+    fn column_mut(&mut self, location: (i32, i32)) -> Option<&mut ChunkColumn> {
+        self.get_mut(&location).map(|loaded| &mut loaded.column)
+    }
+}
+
+/// Clears the packets of the column at `location` and of those around it,
+/// which light may have spread to.
+fn invalidate_around(columns: &mut LoadedColumns, (x, z): (i32, i32)) {
+    for location_x in x - 1..=x + 1 {
+        for location_z in z - 1..=z + 1 {
+            if let Some(loaded) = columns.get_mut(&(location_x, location_z)) {
+                loaded.invalidate();
+            }
+        }
+    }
+}
+
+/// Columns a player has to unload and load once their view moves.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ViewChange {
+    pub unload: Vec<(i32, i32)>,
+    /// Nearest to the new center first.
+    pub load: Vec<(i32, i32)>,
+}
+
+/// Returns the columns to unload and load when the view of a player moves
+/// from `old_center` to `center`, `sent` being the columns they were sent.
+/// Columns are in view up to `view_distance` columns away in each direction.
+pub fn view_change(
+    old_center: Option<(i32, i32)>,
+    center: (i32, i32),
+    view_distance: i32,
+    sent: &HashSet<(i32, i32)>,
+) -> ViewChange {
+    if old_center == Some(center) {
+        return ViewChange::default();
+    }
+    let in_view = |(x, z): (i32, i32)| {
+        (x - center.0).abs() <= view_distance && (z - center.1).abs() <= view_distance
+    };
+    let mut unload: Vec<_> = sent.iter().copied().filter(|location| !in_view(*location)).collect();
+    unload.sort_unstable();
+
+    let mut load = Vec::new();
+    for x in center.0 - view_distance..=center.0 + view_distance {
+        for z in center.1 - view_distance..=center.1 + view_distance {
+            if !sent.contains(&(x, z)) {
+                load.push((x, z));
+            }
+        }
+    }
+    let distance = |(x, z): (i32, i32)| {
+        let (dx, dz) = (x - center.0, z - center.1);
+        dx * dx + dz * dz
+    };
+    load.sort_by_key(|location| (distance(*location), *location));
+    ViewChange { unload, load }
+}
+
+/// The chunk columns of a world, loaded from its region files or generated
+/// while players look at them. Readers only share a lock, columns are loaded,
+/// generated and lit without holding it, then only their borders are lit
+/// with the lock held.
+pub struct World {
+    generator: Arc<dyn WorldGenerator>,
+    regions: Mutex<RegionStorage>,
+    columns: RwLock<LoadedColumns>,
+}
+
+impl World {
+    pub fn new(generator: Arc<dyn WorldGenerator>, regions: RegionStorage) -> Self {
+        Self {
+            generator,
+            regions: Mutex::new(regions),
+            columns: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn generator(&self) -> &dyn WorldGenerator {
+        &*self.generator
+    }
+
+    /// Counts one more player looking at the column at `location`, loading
+    /// it first if needed. The column is generated when it could not be read
+    /// from its region file, the error is returned once it is. Such columns
+    /// are never saved, the region file keeps the chunk it could not read.
+    pub fn add_viewer(&self, location: (i32, i32)) -> Result<(), AnvilErr> {
+        if let Some(loaded) = self.columns.read().unwrap().get(&location) {
+            loaded.viewers.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let loaded = self.regions.lock().unwrap().load_column(location);
+        let (mut column, result) = match loaded {
+            Ok(Some(column)) => (column, Ok(())),
+            Ok(None) => (self.generator.generate(location), Ok(())),
+            Err(e) => (self.generator.generate(location), Err(e)),
+        };
+        let unsaved = result.is_err();
+        let lit = column.is_lit();
+        if !lit {
+            light::light_column(&mut column, location);
+        }
+        let mut columns = self.columns.write().unwrap();
+        // another player may have loaded it in the meantime
+        if let Entry::Vacant(entry) = columns.entry(location) {
+            entry.insert(LoadedColumn::new(column, unsaved));
+            if !lit {
+                light::light_borders(&mut *columns, location);
+            }
+            invalidate_around(&mut columns, location);
+        }
+        columns[&location].viewers.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Counts one less player looking at the column at `location`, it stays
+    /// loaded until `unload_unused`.
+    pub fn remove_viewer(&self, location: (i32, i32)) {
+        if let Some(loaded) = self.columns.read().unwrap().get(&location) {
+            let _ = loaded
+                .viewers
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |viewers| viewers.checked_sub(1));
+        }
+    }
+
+    /// Returns how many players look at the column at `location`, `None` if
+    /// it is not loaded.
+    pub fn viewers(&self, location: (i32, i32)) -> Option<usize> {
+        let columns = self.columns.read().unwrap();
+        columns.get(&location).map(|loaded| loaded.viewers.load(Ordering::Relaxed))
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.columns.read().unwrap().len()
+    }
+
+    /// Returns the packets sending the column at `location` to a client of
+    /// `version`, encoded once until the column changes.
+    pub fn encoded_chunk(&self, location: (i32, i32), version: ProtocolVersion) -> Option<Arc<EncodedChunk>> {
+        let columns = self.columns.read().unwrap();
+        let loaded = columns.get(&location)?;
+        let mut encoded = loaded.encoded.lock().unwrap();
+        let chunk = encoded
+            .entry(version)
+            .or_insert_with(|| Arc::new(EncodedChunk::new(&loaded.column, version)));
+        Some(chunk.clone())
+    }
+
+    /// Calls `f` with the column at `location` if it is loaded.
+    pub fn with_column<R>(&self, location: (i32, i32), f: impl FnOnce(&ChunkColumn) -> R) -> Option<R> {
+        let columns = self.columns.read().unwrap();
+        columns.get(&location).map(|loaded| f(&loaded.column))
+    }
+
+    /// Calls `f` to change the column at `location` if it is loaded. Light is
+    /// left as it is, `set_block` relights around the block it sets.
+    pub fn modify_column<R>(&self, location: (i32, i32), f: impl FnOnce(&mut ChunkColumn) -> R) -> Option<R> {
+        let mut columns = self.columns.write().unwrap();
+        let loaded = columns.get_mut(&location)?;
+        loaded.invalidate();
+        Some(f(&mut loaded.column))
+    }
+
+    /// Sets a block at a position in the world and relights around it. Returns
+    /// `false` if its column is not loaded or `y` is outside of the world.
+    pub fn set_block(&self, (x, y, z): (i32, i32, i32), block: BlockState) -> bool {
+        let mut columns = self.columns.write().unwrap();
+        if !light::set_block(&mut *columns, (x, y, z), block) {
+            return false;
+        }
+        invalidate_around(&mut columns, (x >> 4, z >> 4));
+        true
+    }
+
+    /// Writes the columns changed since they were last saved to their region
+    /// files. Every column is tried, the first error is returned.
+    pub fn save(&self) -> Result<usize, AnvilErr> {
+        let mut columns = self.columns.write().unwrap();
+        self.save_columns(columns.values_mut())
+    }
+
+    /// Saves and unloads the columns no player looks at, returning how many
+    /// were unloaded. Columns which could not be saved stay loaded.
+    pub fn unload_unused(&self) -> Result<usize, AnvilErr> {
+        let mut columns = self.columns.write().unwrap();
+        let unused = columns
+            .values_mut()
+            .filter(|loaded| loaded.viewers.load(Ordering::Relaxed) == 0);
+        let result = self.save_columns(unused);
+        let count = columns.len();
+        columns.retain(|_, loaded| *loaded.viewers.get_mut() > 0 || loaded.needs_saving());
+        let unloaded = count - columns.len();
+        result.map(|_| unloaded)
+    }
+
+    fn save_columns<'a>(&self, columns: impl Iterator<Item = &'a mut LoadedColumn>) -> Result<usize, AnvilErr> {
+        let mut regions = self.regions.lock().unwrap();
+        let (mut saved, mut error) = (0, None);
+        for loaded in columns.filter(|loaded| loaded.needs_saving()) {
+            match regions.save_column(&mut loaded.column) {
+                Ok(()) => saved += 1,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(saved),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anvil::Region;
+    use crate::block::BlockKind;
+    use crate::generator::Superflat;
+    use crate::nbt;
+    use crate::packet::light::LightKind;
+
+    use std::fs;
+
+    fn world(name: &str) -> (World, std::path::PathBuf) {
+        let directory = std::env::temp_dir().join(format!("mycraft-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let world = World::new(Arc::new(Superflat::default()), RegionStorage::new(&directory));
+        (world, directory)
+    }
+
+    #[test]
+    fn views_change_when_players_cross_columns() {
+        let first = view_change(None, (0, 0), 2, &HashSet::new());
+        assert!(first.unload.is_empty());
+        assert_eq!(first.load.len(), 25);
+        assert_eq!(first.load[..5], [(0, 0), (-1, 0), (0, -1), (0, 1), (1, 0)]);
+        assert_eq!(first.load[24], (2, 2));
+        let sent: HashSet<_> = first.load.into_iter().collect();
+        assert_eq!(view_change(Some((0, 0)), (0, 0), 2, &sent), ViewChange::default());
+
+        // one column east, the western row goes and an eastern one comes
+        let step = view_change(Some((0, 0)), (1, 0), 2, &sent);
+        assert_eq!(step.unload, [(-2, -2), (-2, -1), (-2, 0), (-2, 1), (-2, 2)]);
+        assert_eq!(step.load, [(3, 0), (3, -1), (3, 1), (3, -2), (3, 2)]);
+
+        // far away, everything goes and a whole view comes
+        let teleport = view_change(Some((0, 0)), (100, -100), 2, &sent);
+        assert_eq!(teleport.unload.len(), 25);
+        assert_eq!(teleport.load.len(), 25);
+        assert_eq!(teleport.load[0], (100, -100));
+    }
+
+    #[test]
+    fn unused_columns_are_saved_and_unloaded() {
+        let (world, directory) = world("unload");
+        world.add_viewer((0, 0)).unwrap();
+        world.add_viewer((0, 0)).unwrap();
+        world.add_viewer((1, 0)).unwrap();
+        assert_eq!(world.viewers((0, 0)), Some(2));
+        let torch = BlockKind::Torch.default_state();
+        assert!(world.set_block((3, 4, 5), torch));
+
+        world.remove_viewer((0, 0));
+        world.remove_viewer((1, 0));
+        assert_eq!(world.unload_unused().unwrap(), 1);
+        assert_eq!(world.viewers((1, 0)), None);
+        assert!(directory.join("r.0.0.mca").exists());
+
+        world.remove_viewer((0, 0));
+        world.remove_viewer((0, 0));
+        assert_eq!(world.viewers((0, 0)), Some(0));
+        assert_eq!(world.unload_unused().unwrap(), 1);
+        assert_eq!(world.loaded_count(), 0);
+        assert!(!world.set_block((3, 4, 5), torch));
+
+        // loaded again from the region file rather than generated
+        world.add_viewer((0, 0)).unwrap();
+        assert_eq!(world.with_column((0, 0), |column| column.block_at(3, 4, 5)), Some(torch));
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn chunks_which_could_not_be_read_are_not_overwritten() {
+        let (world, directory) = world("corrupt");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("r.0.0.mca");
+        let chunk = nbt::snbt::parse(
+            r#"{DataVersion: 2230, Level: {xPos: 0, zPos: 0, Status: "full", Sections: [
+                {Y: 0b, Palette: [{Name: "minecraft:cheese"}], BlockStates: [L; 0L]}
+            ]}}"#,
+        )
+        .unwrap();
+        Region::open(&path).unwrap().write_chunk((0, 0), &chunk).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        assert!(matches!(world.add_viewer((0, 0)), Err(AnvilErr::UnknownBlock(_))));
+        assert!(world.set_block((3, 4, 5), BlockKind::Torch.default_state()));
+        assert_eq!(world.save().unwrap(), 0);
+        world.remove_viewer((0, 0));
+        assert_eq!(world.unload_unused().unwrap(), 1);
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn light_spreading_into_neighbours_is_saved() {
+        let (world, directory) = world("light");
+        world.add_viewer((0, 0)).unwrap();
+        world.add_viewer((1, 0)).unwrap();
+        assert_eq!(world.save().unwrap(), 2);
+        assert_eq!(world.save().unwrap(), 0);
+
+        // the glowstone lights the column next to it, which has to be saved too
+        assert!(world.set_block((15, 4, 0), BlockKind::Glowstone.default_state()));
+        assert_eq!(world.save().unwrap(), 2);
+        world.remove_viewer((0, 0));
+        world.remove_viewer((1, 0));
+        assert_eq!(world.unload_unused().unwrap(), 2);
+        world.add_viewer((1, 0)).unwrap();
+        let light = world.with_column((1, 0), |column| column.light().get(LightKind::Block, 0, 4, 0));
+        assert_eq!(light, Some(14));
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn encoded_chunks_are_cached_until_changed() {
+        let (world, directory) = world("encoded");
+        assert!(world.encoded_chunk((0, 0), ProtocolVersion::LATEST).is_none());
+        world.add_viewer((0, 0)).unwrap();
+        world.add_viewer((1, 0)).unwrap();
+        let chunk = world.encoded_chunk((0, 0), ProtocolVersion::LATEST).unwrap();
+        let neighbour = world.encoded_chunk((1, 0), ProtocolVersion::LATEST).unwrap();
+        assert!(Arc::ptr_eq(&chunk, &world.encoded_chunk((0, 0), ProtocolVersion::LATEST).unwrap()));
+        let old = world.encoded_chunk((0, 0), ProtocolVersion::V1_14_4).unwrap();
+        assert_ne!(old.chunk_data, chunk.chunk_data);
+
+        // the glowstone lights the column next to it too
+        assert!(world.set_block((15, 4, 0), BlockKind::Glowstone.default_state()));
+        let changed = world.encoded_chunk((0, 0), ProtocolVersion::LATEST).unwrap();
+        assert_ne!(changed, chunk);
+        assert_ne!(world.encoded_chunk((1, 0), ProtocolVersion::LATEST).unwrap(), neighbour);
+
+        world.modify_column((0, 0), |column| column.set_block_at(0, 10, 0, BlockState::AIR));
+        assert!(!Arc::ptr_eq(&changed, &world.encoded_chunk((0, 0), ProtocolVersion::LATEST).unwrap()));
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn viewers_are_counted_across_threads() {
+        let (world, directory) = world("threads");
+        let world = Arc::new(world);
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let world = world.clone();
+                std::thread::spawn(move || {
+                    for x in 0..4 {
+                        world.add_viewer((x, i % 2)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads.into_iter() {
+            thread.join().unwrap();
+        }
+        assert_eq!(world.loaded_count(), 8);
+        assert_eq!(world.viewers((3, 1)), Some(4));
+        let _ = fs::remove_dir_all(&directory);
+    }
+}